@@ -0,0 +1,272 @@
+//! Incremental version of `ComputeGlyphHash`
+
+use core::hash::{BuildHasher, Hasher};
+use simd_aes::Block;
+use super::GlyphHashParams;
+
+/// Streaming glyph hasher, producing the same result as `GlyphHashParams::hash`.
+///
+/// The message length is folded into the initial state, so no block can be
+/// consumed before the length is known. With a declared length (`with_len`)
+/// the hasher only buffers up to 16 bytes. With a deferred length (`new`)
+/// all input is kept until `declare_len` or `finalize` is called.
+#[derive(Clone, Debug)]
+pub struct GlyphHasher {
+    /// The hash variant
+    params: GlyphHashParams,
+    /// Hash state, valid once the length is declared
+    state: Block,
+    /// Bytes of the current, incomplete block
+    buffer: [u8; 16],
+    /// Number of valid bytes in `buffer`
+    buffered: usize,
+    /// The declared message length
+    len: Option<usize>,
+    /// Number of bytes consumed so far
+    written: usize,
+    /// Input received before the length was declared
+    pending: Vec<u8>,
+}
+
+impl GlyphHasher {
+    /// Construct a hasher for a message of unknown length
+    pub fn new(params: &GlyphHashParams) -> Self {
+        Self {
+            params: *params,
+            state: Block::ZERO,
+            buffer: [0; 16],
+            buffered: 0,
+            len: None,
+            written: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Construct a hasher for a message of exactly `len` bytes
+    pub fn with_len(params: &GlyphHashParams, len: usize) -> Self {
+        let mut hasher = Self::new(params);
+        hasher.declare_len(len);
+        hasher
+    }
+
+    /// The declared message length, if any
+    pub fn declared_len(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Number of bytes fed to the hasher so far
+    pub fn written(&self) -> usize {
+        self.written + self.pending.len()
+    }
+
+    /// Declare the total message length and consume any pending input.
+    ///
+    /// # Panics
+    /// If the length was already declared, or is shorter than the input
+    /// received so far.
+    pub fn declare_len(&mut self, len: usize) {
+        assert!(self.len.is_none(), "glyph hash length declared twice");
+        self.len = Some(len);
+        self.state = self.params.initial_state(len);
+        let pending = core::mem::take(&mut self.pending);
+        self.consume(&pending);
+    }
+
+    /// Feed the next chunk of the message
+    ///
+    /// # Panics
+    /// If the input exceeds the declared length.
+    pub fn update(&mut self, data: &[u8]) {
+        if self.len.is_none() {
+            self.pending.extend_from_slice(data);
+            return;
+        }
+        self.consume(data);
+    }
+
+    fn consume(&mut self, mut data: &[u8]) {
+        let len = self.len.unwrap();
+        self.written += data.len();
+        assert!(self.written <= len, "glyph hash input exceeds declared length {}", len);
+
+        if self.buffered > 0 {
+            let take = data.len().min(16 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < 16 {
+                return;
+            }
+            self.state = self.params.absorb_block(self.state, Block::from_array(self.buffer));
+            self.buffered = 0;
+        }
+
+        let mut chunks = data.chunks_exact(16);
+        for chunk in chunks.by_ref() {
+            let chunk: &[u8; 16] = chunk.try_into().unwrap();
+            self.state = self.params.absorb_block(self.state, Block::from_array(*chunk));
+        }
+
+        let remainder = chunks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffered = remainder.len();
+    }
+
+    /// Finish the hash. A deferred length is taken to be the input length.
+    ///
+    /// # Panics
+    /// If less input was fed than the declared length.
+    pub fn finalize(mut self) -> Block {
+        if self.len.is_none() {
+            self.declare_len(self.pending.len());
+        }
+        let len = self.len.unwrap();
+        assert_eq!(self.written, len, "glyph hash input shorter than declared length");
+        return self.params.absorb_tail(self.state, &self.buffer[..self.buffered]);
+    }
+}
+
+impl Default for GlyphHasher {
+    fn default() -> Self {
+        Self::new(&GlyphHashParams::REFTERM)
+    }
+}
+
+impl Hasher for GlyphHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    /// The low 64 bits of the glyph hash
+    fn finish(&self) -> u64 {
+        let hash = self.clone().finalize().to_array();
+        u64::from_ne_bytes(hash[..8].try_into().unwrap())
+    }
+}
+
+/// `BuildHasher` producing deferred-length `GlyphHasher`s, for use with
+/// `HashMap::with_hasher`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuildGlyphHasher(pub GlyphHashParams);
+
+impl BuildHasher for BuildGlyphHasher {
+    type Hasher = GlyphHasher;
+
+    fn build_hasher(&self) -> GlyphHasher {
+        GlyphHasher::new(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::ComputeGlyphHash;
+    use crate::test_util::{message, variants};
+
+    const LENGTHS: [usize; 8] = [0, 1, 15, 16, 17, 31, 32, 33];
+
+    /// Hash `data` split at `first` and `second` into three chunks, with the
+    /// length declared up front and deferred
+    fn check_splits(params: &GlyphHashParams, data: &[u8], first: usize, second: usize) {
+        let expected = params.hash(data);
+        let (head, rest) = data.split_at(first);
+        let (middle, tail) = rest.split_at(second - first);
+
+        let mut declared = GlyphHasher::with_len(params, data.len());
+        let mut deferred = GlyphHasher::new(params);
+        for chunk in [head, middle, tail] {
+            declared.update(chunk);
+            deferred.update(chunk);
+        }
+        assert_eq!(declared.written(), data.len());
+        assert_eq!(deferred.written(), data.len());
+        assert_eq!(deferred.declared_len(), None);
+        assert_eq!(declared.finalize(), expected, "{params:?}, {} bytes split at {first}, {second}", data.len());
+        assert_eq!(deferred.finalize(), expected, "{params:?}, {} bytes split at {first}, {second}", data.len());
+    }
+
+    #[test]
+    fn chunk_splits_match_hash() {
+        for params in variants() {
+            for len in LENGTHS {
+                let data = message(len);
+                for first in 0..=len {
+                    for second in first..=len {
+                        check_splits(&params, &data, first, second);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        for len in LENGTHS {
+            let data = message(len);
+            let mut hasher = GlyphHasher::default();
+            for byte in &data {
+                hasher.update(core::slice::from_ref(byte));
+            }
+            hasher.declare_len(len);
+            assert_eq!(hasher.declared_len(), Some(len));
+            assert_eq!(hasher.finalize(), ComputeGlyphHash(&data));
+        }
+    }
+
+    #[test]
+    fn finish_matches_finalize() {
+        for len in LENGTHS {
+            let data = message(len);
+            let mut hasher = GlyphHasher::default();
+            hasher.write(&data);
+            let low = u64::from_ne_bytes(ComputeGlyphHash(&data).to_array()[..8].try_into().unwrap());
+            assert_eq!(hasher.finish(), low);
+            // `finish` leaves the hasher usable
+            assert_eq!(hasher.finish(), low);
+            assert_eq!(hasher.finalize(), ComputeGlyphHash(&data));
+        }
+    }
+
+    #[test]
+    fn hash_map_lookup() {
+        let mut map = HashMap::with_hasher(BuildGlyphHasher::default());
+        let glyphs = ["a", "ab", "🙂", "Hello Casey!", "0123456789abcdefghij"];
+        for (index, glyph) in glyphs.iter().enumerate() {
+            map.insert(*glyph, index);
+        }
+        assert_eq!(map.len(), glyphs.len());
+        for (index, glyph) in glyphs.iter().enumerate() {
+            assert_eq!(map.get(glyph), Some(&index));
+        }
+        assert_eq!(map.get("b"), None);
+
+        let build = BuildGlyphHasher::default();
+        assert_eq!(build.hash_one("ab"), build.hash_one("ab"));
+        assert_ne!(build.hash_one("ab"), build.hash_one("ba"));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds declared length")]
+    fn over_length_panics() {
+        let mut hasher = GlyphHasher::with_len(&GlyphHashParams::REFTERM, 17);
+        hasher.update(&message(16));
+        hasher.update(&message(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds declared length")]
+    fn deferred_over_length_panics() {
+        let mut hasher = GlyphHasher::new(&GlyphHashParams::REFTERM);
+        hasher.update(&message(18));
+        hasher.declare_len(17);
+    }
+
+    #[test]
+    #[should_panic(expected = "shorter than declared length")]
+    fn under_length_panics() {
+        let mut hasher = GlyphHasher::with_len(&GlyphHashParams::REFTERM, 17);
+        hasher.update(&message(16));
+        hasher.finalize();
+    }
+}
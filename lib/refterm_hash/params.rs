@@ -0,0 +1,281 @@
+//! Parameters of `ComputeGlyphHash`, to describe refterm forks and variants
+
+use simd_aes::{Block, SimdAes};
+use super::DEFAULT_SEED;
+
+/// A 64-bit lane of the state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lane {
+    /// Bytes 0 to 7
+    Low,
+    /// Bytes 8 to 15
+    High,
+}
+
+impl Lane {
+    /// Offset of the lane's first byte
+    #[inline]
+    pub const fn offset(self) -> usize {
+        match self {
+            Lane::Low => 0,
+            Lane::High => 8,
+        }
+    }
+}
+
+/// How the message length is mixed into the initial state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LengthEncoding {
+    /// The length is not mixed in
+    None,
+    /// Little-endian `u64` in the given lane
+    Le(Lane),
+    /// Big-endian `u64` in the given lane
+    Be(Lane),
+}
+
+/// How the last, incomplete block is padded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TailPadding {
+    /// Fill with zeros; the tail block is hashed even if it is empty
+    Zeros,
+    /// Fill with zeros; an empty tail block is skipped
+    ZerosSkipEmpty,
+    /// Fill with a constant byte; the tail block is always hashed
+    Fill(u8),
+    /// Append a marker byte, then fill with zeros
+    Marker(u8),
+}
+
+/// A variant of the glyph hash: the seed, the number of `aes_dec` rounds
+/// with a zero key per full block and on the tail block, the length
+/// encoding and the tail padding rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphHashParams {
+    /// Xored into the initial state
+    pub seed: Block,
+    /// `aes_dec` rounds after each full block
+    pub block_rounds: usize,
+    /// `aes_dec` rounds after the tail block
+    pub tail_rounds: usize,
+    /// Length encoding in the initial state
+    pub length: LengthEncoding,
+    /// Padding of the tail block
+    pub padding: TailPadding,
+}
+
+impl GlyphHashParams {
+    /// The hash as implemented in refterm
+    pub const REFTERM: GlyphHashParams = GlyphHashParams {
+        seed: DEFAULT_SEED,
+        block_rounds: 4,
+        tail_rounds: 4,
+        length: LengthEncoding::Le(Lane::Low),
+        padding: TailPadding::Zeros,
+    };
+
+    /// The length of a `len`-byte message, as laid out in the initial state
+    #[inline]
+    pub fn length_bytes(&self, len: usize) -> Block {
+        let mut bytes = [0_u8; 16];
+        match self.length {
+            LengthEncoding::None => {}
+            LengthEncoding::Le(lane) => {
+                bytes[lane.offset()..][..8].copy_from_slice(&(len as u64).to_le_bytes());
+            }
+            LengthEncoding::Be(lane) => {
+                bytes[lane.offset()..][..8].copy_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        return Block::from_array(bytes);
+    }
+
+    /// Initial hash state for a message of `len` bytes
+    #[inline]
+    pub fn initial_state(&self, len: usize) -> Block {
+        return self.length_bytes(len) ^ self.seed;
+    }
+
+    /// The padded tail block for a tail of up to 15 bytes, or `None` if no
+    /// tail block is hashed
+    pub fn tail_block(&self, tail: &[u8]) -> Option<Block> {
+        let mut block = [0_u8; 16];
+        match self.padding {
+            TailPadding::Zeros => {}
+            TailPadding::ZerosSkipEmpty => {
+                if tail.is_empty() {
+                    return None;
+                }
+            }
+            TailPadding::Fill(fill) => block[tail.len()..].fill(fill),
+            TailPadding::Marker(marker) => block[tail.len()] = marker,
+        }
+        block[..tail.len()].copy_from_slice(tail);
+        return Some(Block::from_array(block));
+    }
+
+    /// Xor a full block into the state and run the block rounds
+    #[inline]
+    pub fn absorb_block(&self, hash: Block, value: Block) -> Block {
+        return aes_dec_rounds(hash ^ value, self.block_rounds);
+    }
+
+    /// Pad the tail, xor it into the state and run the tail rounds
+    pub fn absorb_tail(&self, hash: Block, tail: &[u8]) -> Block {
+        match self.tail_block(tail) {
+            Some(value) => aes_dec_rounds(hash ^ value, self.tail_rounds),
+            None => hash,
+        }
+    }
+
+    /// Inverse of the block rounds
+    #[inline]
+    pub fn inv_block_rounds(&self, hash: Block) -> Block {
+        return inv_aes_dec_rounds(hash, self.block_rounds);
+    }
+
+    /// Inverse of the tail rounds
+    #[inline]
+    pub fn inv_tail_rounds(&self, hash: Block) -> Block {
+        return inv_aes_dec_rounds(hash, self.tail_rounds);
+    }
+
+    /// Hash `data` with this variant
+    pub fn hash(&self, data: &[u8]) -> Block {
+        let mut hash = self.initial_state(data.len());
+
+        let mut chunks = data.chunks_exact(16);
+        for chunk in chunks.by_ref() {
+            let chunk: &[u8; 16] = chunk.try_into().unwrap();
+            hash = self.absorb_block(hash, Block::from_array(*chunk));
+        }
+
+        return self.absorb_tail(hash, chunks.remainder());
+    }
+}
+
+impl Default for GlyphHashParams {
+    fn default() -> Self {
+        Self::REFTERM
+    }
+}
+
+/// `rounds` `aes_dec` rounds with a zero key
+#[inline]
+pub fn aes_dec_rounds(mut hash: Block, rounds: usize) -> Block {
+    let zero = Block::ZERO;
+    for _ in 0..rounds {
+        hash = hash.aes_dec(zero);
+    }
+    return hash;
+}
+
+/// Inverse of `rounds` `aes_dec` rounds with a zero key
+#[inline]
+pub fn inv_aes_dec_rounds(hash: Block, rounds: usize) -> Block {
+    return hash.inv_aes_dec_rounds(Block::ZERO, rounds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputeGlyphHash, chosen_prefix, invert, padding_collision, prefix_collision, preimage};
+    use crate::test_util::{message, variants};
+
+    /// refterm's `ComputeGlyphHash`, step by step
+    fn refterm_hash(data: &[u8]) -> Block {
+        let mut hash = Block::from_u64s([data.len() as u64, 0]) ^ DEFAULT_SEED;
+        let mut chunks = data.chunks_exact(16);
+        for chunk in chunks.by_ref() {
+            hash ^= Block::from_array(chunk.try_into().unwrap());
+            for _ in 0..4 {
+                hash = hash.aes_dec(Block::ZERO);
+            }
+        }
+        let mut overhang = [0_u8; 16];
+        overhang[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+        hash ^= Block::from_array(overhang);
+        for _ in 0..4 {
+            hash = hash.aes_dec(Block::ZERO);
+        }
+        hash
+    }
+
+    #[test]
+    fn refterm_matches_compute_glyph_hash() {
+        for len in 0..=64 {
+            let data = message(len);
+            let expected = refterm_hash(&data);
+            assert_eq!(GlyphHashParams::REFTERM.hash(&data), expected);
+            assert_eq!(ComputeGlyphHash(&data), expected);
+        }
+        assert_eq!(GlyphHashParams::default(), GlyphHashParams::REFTERM);
+        assert_eq!(variants().next(), Some(GlyphHashParams::REFTERM));
+    }
+
+    #[test]
+    fn length_lanes() {
+        let len = 0x0102_0304_0506_0708;
+        for (length, bytes) in [
+            (LengthEncoding::Le(Lane::Low), [8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (LengthEncoding::Le(Lane::High), [0, 0, 0, 0, 0, 0, 0, 0, 8, 7, 6, 5, 4, 3, 2, 1]),
+            (LengthEncoding::Be(Lane::Low), [1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (LengthEncoding::Be(Lane::High), [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]),
+            (LengthEncoding::None, [0; 16]),
+        ] {
+            let params = GlyphHashParams { length, ..GlyphHashParams::REFTERM };
+            assert_eq!(params.length_bytes(len).to_array(), bytes);
+        }
+    }
+
+    #[test]
+    fn invert_variants() {
+        for params in variants() {
+            for len in 0..16 {
+                let hash = params.hash(&message(len));
+                let recovered = invert(&params, hash).expect("short messages can be inverted");
+                assert!(recovered.len() <= len);
+                assert_eq!(params.hash(&recovered), hash, "{params:?}, {len} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn padding_collision_variants() {
+        for params in variants() {
+            for len in [0, 1, 14, 15, 16, 17, 20, 31, 32, 33] {
+                let data = message(len);
+                let Some(forgery) = padding_collision(&params, &data) else {
+                    continue;
+                };
+                assert_ne!(forgery, data);
+                assert_eq!(params.hash(&forgery), params.hash(&data), "{params:?}, {len} bytes");
+            }
+            // Only the marker byte keeps the padding apart from the message
+            let vulnerable = !matches!(params.padding, TailPadding::Marker(_));
+            assert_eq!(padding_collision(&params, &message(20)).is_some(), vulnerable, "{params:?}");
+        }
+    }
+
+    #[test]
+    fn prefix_and_preimage_variants() {
+        let target = Block::from_array(*b"0123456789abcdef");
+        for params in variants() {
+            for len in [0, 1, 15, 16, 17, 33] {
+                let data = message(len);
+
+                let mut prefixed = prefix_collision(&params, &data).to_array().to_vec();
+                prefixed.extend_from_slice(&data);
+                assert_eq!(params.hash(&prefixed), params.hash(&data), "{params:?}, {len} bytes");
+
+                let image = preimage(&params, &data, target);
+                assert!(image.ends_with(&data));
+                assert_eq!(params.hash(&image), target, "{params:?}, {len} bytes");
+
+                let forged = chosen_prefix(&params, &data, target);
+                assert!(forged.starts_with(&data));
+                assert_eq!(params.hash(&forged), target, "{params:?}, {len} bytes");
+            }
+        }
+    }
+}
@@ -0,0 +1,537 @@
+#![allow(clippy::needless_return)]
+//! The glyph hash used by refterm (`ComputeGlyphHash`), its inverse steps
+//! and the attacks built on top of them.
+//!
+//! The hash runs four `aesdec` rounds with a zero key per 16-byte block,
+//! starting from `[len, 0] ^ DEFAULT_SEED`. Every step consuming bytes is
+//! invertible, so the hash can be run backwards from any target value.
+//! `GlyphHashParams` describes variants of this construction; every
+//! function taking it works for any variant.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use srng::{Filler, RandomSource};
+use simd_aes::{Bitsliced, Block, Block4, Isa, Kernel, SimdAes, isa};
+
+mod params;
+pub use params::{GlyphHashParams, Lane, LengthEncoding, TailPadding, aes_dec_rounds, inv_aes_dec_rounds};
+mod glyph_hasher;
+pub use glyph_hasher::{GlyphHasher, BuildGlyphHasher};
+mod reverse_hasher;
+pub use reverse_hasher::ReverseGlyphHasher;
+#[cfg(test)]
+mod test_util;
+
+/// The seed refterm mixes into the initial hash state
+pub const DEFAULT_SEED: Block = Block::from_array([
+    178, 201, 95, 240, 40, 41, 143, 216,
+    2, 209, 178, 114, 232, 4, 176, 188,
+]);
+
+/// The glyph hash, as implemented in refterm
+#[allow(non_snake_case)]
+pub fn ComputeGlyphHash(data: &[u8]) -> Block {
+    return GlyphHashParams::REFTERM.hash(data);
+}
+
+/// Inverse of a single `aes_dec` round with the given key
+pub fn inv_aes_dec(data: Block, key: Block) -> Block {
+    return data.inv_aes_dec(key);
+}
+
+/// Inverse of four `aes_dec` rounds with a zero key, i.e. of one refterm
+/// block step
+pub fn inv_aes_decx4(hash: Block) -> Block {
+    return inv_aes_dec_rounds(hash, 4);
+}
+
+/// Inverse of `rounds` `aes_dec` rounds with a zero key on `N` independent
+/// blocks: four at a time so that the rounds of different blocks overlap,
+/// or run in one instruction with VAES, or 64 at a time bitsliced without
+/// AES instructions. The path is picked by `isa`, at compile time for the
+/// markers `dispatch` passes.
+#[inline(always)]
+fn inv_aes_dec_rounds_batch<I: Isa, const N: usize>(isa: I, blocks: [Block; N], rounds: usize) -> [Block; N] {
+    let mut out = blocks;
+    if !isa.backend().has_aes() {
+        for chunk in out.chunks_mut(Bitsliced::<u64>::BLOCKS) {
+            let sliced = Bitsliced::<u64>::pack(chunk);
+            sliced.inv_aes_dec_rounds(Bitsliced::default(), rounds).unpack(chunk);
+        }
+        return out;
+    }
+
+    if rounds == 0 {
+        return out;
+    }
+    // Round by round over all chunks, so that the chunks overlap instead of
+    // waiting for the latency of every round
+    let (chunks, rest) = out.as_chunks_mut::<4>();
+    let zero = Block4::ZERO;
+    for chunk in chunks.iter_mut() {
+        *chunk = isa.mix_columns4(Block4(*chunk)).0;
+    }
+    for _ in 1..rounds {
+        for chunk in chunks.iter_mut() {
+            *chunk = isa.aes_enc4(Block4(*chunk), zero).0;
+        }
+    }
+    for chunk in chunks.iter_mut() {
+        *chunk = isa.aes_enc_last4(Block4(*chunk), zero).0;
+    }
+    for block in rest {
+        *block = isa.inv_aes_dec_rounds(*block, Block::ZERO, rounds);
+    }
+    return out;
+}
+
+/// `inv_aes_decx4` on `N` independent blocks
+#[inline]
+pub fn inv_aes_decx4_batch<const N: usize>(hashes: [Block; N]) -> [Block; N] {
+    return inv_aes_dec_rounds_batch(isa::Detected, hashes, 4);
+}
+
+/// Compute a 16-byte block that, placed in front of a `count`-byte message,
+/// drives the hash state to `target_hash` before the message is consumed.
+pub fn single_prefix(params: &GlyphHashParams, count: usize, target_hash: Block) -> Block {
+    // The first stage looks like this:
+    //     Hash = dec^4(Count ^ Seed ^ Chunk)
+    // To get the chunk, we need to reverse these:
+    //     dec^-4(Hash) = Count ^ Seed ^ Chunk
+    //     Chunk = dec^-4(Hash) ^ Count ^ Seed
+    // To create a one-prefix initialization, we want:
+    //     Hash = target
+    //     Count = Count + 16
+    return ReverseGlyphHasher::with_state(params, target_hash, 16, count + 16).prefix_block();
+}
+
+/// `single_prefix` for `N` independent target states
+#[inline]
+pub fn single_prefix_batch<const N: usize>(
+    params: &GlyphHashParams,
+    count: usize,
+    target_hashes: [Block; N],
+) -> [Block; N] {
+    return single_prefix_batch_with(isa::Detected, params, count, target_hashes);
+}
+
+/// `single_prefix_batch` with the rounds of `isa`
+#[inline(always)]
+fn single_prefix_batch_with<I: Isa, const N: usize>(
+    isa: I,
+    params: &GlyphHashParams,
+    count: usize,
+    target_hashes: [Block; N],
+) -> [Block; N] {
+    let initial = params.initial_state(count + 16);
+    let prefixes = inv_aes_dec_rounds_batch(isa, target_hashes, params.block_rounds);
+    return prefixes.map(|prefix| prefix ^ initial);
+}
+
+/// Run the hash backwards from `hash` over all of `data`, including the
+/// padded tail block, yielding the state required before `data`.
+pub fn preimage_prefix_hash(params: &GlyphHashParams, hash: Block, data: &[u8]) -> Block {
+    let mut reverse = ReverseGlyphHasher::new(params, hash, data.len());
+    reverse.update(data);
+    return reverse.state();
+}
+
+/// Run the hash backwards over a single full 16-byte block
+pub fn invert_block(params: &GlyphHashParams, hash: Block, chunk: &[u8]) -> Block {
+    assert_eq!(chunk.len(), 16);
+    let mut reverse = ReverseGlyphHasher::with_state(params, hash, 16, 16);
+    reverse.update(chunk);
+    return reverse.state();
+}
+
+/// Run the hash backwards over the padded tail `suffix` and the block
+/// before it, yielding the state right before that block is xored in.
+pub fn invert_last(params: &GlyphHashParams, suffix: &[u8], hash: Block) -> Block {
+    let mut reverse = ReverseGlyphHasher::new(params, hash, suffix.len() + 16);
+    reverse.update(suffix);
+    return reverse.block_from(Block::ZERO);
+}
+
+/// Check whether all bytes are in `[0-9A-Za-z]`
+#[inline]
+pub fn check_alphanum(bytes: Block) -> bool {
+    return bytes.to_array().iter().all(u8::is_ascii_alphanumeric);
+}
+
+/// `check_alphanum` for `N` blocks, without branching on each byte
+#[inline(always)]
+pub fn check_alphanum_batch<const N: usize>(blocks: [Block; N]) -> [bool; N] {
+    let mut found = [true; N];
+    for (found, block) in found.iter_mut().zip(blocks) {
+        for byte in block.to_array() {
+            *found &= (byte.wrapping_sub(b'0') < 10) | ((byte | 0x20).wrapping_sub(b'a') < 26);
+        }
+    }
+    return found;
+}
+
+fn concat(prefix: Block, target: &[u8]) -> Vec<u8> {
+    let mut image = prefix.to_array().to_vec();
+    image.extend_from_slice(target);
+    image
+}
+
+/// Padding attack: the length is xored into the first bytes of the state and
+/// the tail is padded with a constant, so appending the padding byte and
+/// flipping the first bytes by `len ^ (len + 1)` yields the same hash.
+///
+/// Returns `None` if the variant's padding is not vulnerable, if the message
+/// is too short to absorb the length change, or if the appended byte would
+/// change the number of hashed blocks.
+pub fn padding_collision(params: &GlyphHashParams, message: &[u8]) -> Option<Vec<u8>> {
+    let len = message.len();
+    let fill = match params.padding {
+        TailPadding::Zeros | TailPadding::ZerosSkipEmpty => 0,
+        TailPadding::Fill(fill) => fill,
+        TailPadding::Marker(_) => return None,
+    };
+    let skip_empty = params.padding == TailPadding::ZerosSkipEmpty;
+    if skip_empty && len.is_multiple_of(16) {
+        return None;
+    }
+    // A completed tail turns into a full block, which only matches if the
+    // empty tail is skipped and both use the same rounds.
+    let same_rounds = params.block_rounds == params.tail_rounds;
+    if (len + 1).is_multiple_of(16) && !(skip_empty && same_rounds) {
+        return None;
+    }
+
+    let diff = (params.length_bytes(len) ^ params.length_bytes(len + 1)).to_array();
+    let needed = diff.iter().rposition(|&x| x != 0).map_or(0, |x| x + 1);
+    let mut forgery = message.to_vec();
+    forgery.push(fill);
+    if forgery.len() < needed {
+        return None;
+    }
+    for (byte, flip) in forgery.iter_mut().zip(diff) {
+        *byte ^= flip;
+    }
+    return Some(forgery);
+}
+
+/// Invert attack: recover a message shorter than 16 bytes from its hash.
+///
+/// Due to the padding attack there are more messages with the same hash,
+/// this returns the shortest one.
+pub fn invert(params: &GlyphHashParams, hash: Block) -> Option<Vec<u8>> {
+    for len in 0..16 {
+        let block = ReverseGlyphHasher::new(params, hash, len).prefix_block().to_array();
+        if block[len..].iter().all(|&chr| chr == 0) {
+            return Some(block[..len].to_vec());
+        }
+    }
+    return None;
+}
+
+/// Prefix attack: compute a 16-byte prefix such that `prefix || message`
+/// has the same hash as `message`.
+pub fn prefix_collision(params: &GlyphHashParams, message: &[u8]) -> Block {
+    return single_prefix(params, message.len(), params.initial_state(message.len()));
+}
+
+/// Chosen prefix attack: extend `prefix` with a padding block and a computed
+/// block so that the whole message hashes to `target_hash`.
+pub fn chosen_prefix(params: &GlyphHashParams, prefix: &[u8], target_hash: Block) -> Vec<u8> {
+    let mut message = prefix.to_vec();
+    let remainder = 16 - (message.len() % 16);
+    message.extend((0..remainder).map(|_| b'A'));
+    message.extend((0..16).map(|_| 0));
+    let last = message.len() - 16;
+
+    let mut current = ReverseGlyphHasher::new(params, params.hash(&message), message.len());
+    current.update(&message[last..]);
+    let target = ReverseGlyphHasher::new(params, target_hash, message.len());
+    let block = target.block_from(current.state());
+    message[last..].copy_from_slice(&block.to_array());
+    return message;
+}
+
+/// Preimage attack: compute a 16-byte prefix such that `prefix || suffix`
+/// hashes to `target_hash`, returning the whole message.
+pub fn preimage(params: &GlyphHashParams, suffix: &[u8], target_hash: Block) -> Vec<u8> {
+    let mut reverse = ReverseGlyphHasher::new(params, target_hash, suffix.len() + 16);
+    reverse.update(suffix);
+    return concat(reverse.prefix_block(), suffix);
+}
+
+/// Number of random alphanumeric bytes placed between the computed prefix
+/// and the suffix by `find_ascii_zeros`
+pub const ATTACK_BYTES: usize = 6;
+
+/// A message found by `find_ascii_zeros`
+pub struct AsciiZeros {
+    /// The alphanumeric prefix, the random bytes and the suffix
+    pub message: Vec<u8>,
+    /// Number of candidates tried before finding the message
+    pub iterations: u64,
+}
+
+/// Number of candidates `find_ascii_zeros` evaluates together with AES
+/// instructions
+pub const ASCII_BATCH: usize = 8;
+
+/// Number of candidates `find_ascii_zeros` evaluates together without AES
+/// instructions, one bitsliced batch
+pub const ASCII_BATCH_BITSLICED: usize = 64;
+
+/// ASCII search: find `prefix || random || suffix` with a zero hash, where
+/// the 16-byte prefix and `ATTACK_BYTES` random bytes are alphanumeric.
+///
+/// Runs until a message is found or `stop` is set; `stop` is set when a
+/// message is found so other workers sharing it can bail out. The search
+/// is compiled for the AES backend detected at runtime and evaluates
+/// `ASCII_BATCH` candidates at a time, or `ASCII_BATCH_BITSLICED` with the
+/// software backend. Random characters left over in `rng` are used by the
+/// next call.
+pub fn find_ascii_zeros<R: RandomSource>(
+    params: &GlyphHashParams,
+    suffix: &[u8],
+    rng: &mut Filler<R>,
+    stop: &AtomicBool,
+) -> Option<AsciiZeros> {
+    return simd_aes::dispatch(AsciiSearch { params, suffix, rng, stop });
+}
+
+struct AsciiSearch<'a, R: RandomSource> {
+    params: &'a GlyphHashParams,
+    suffix: &'a [u8],
+    rng: &'a mut Filler<R>,
+    stop: &'a AtomicBool,
+}
+
+impl<R: RandomSource> AsciiSearch<'_, R> {
+    /// The search loop, evaluating `N` candidates at a time
+    #[inline(always)]
+    fn search<I: Isa, const N: usize>(self, isa: I) -> Option<AsciiZeros> {
+        let AsciiSearch { params, suffix, rng, stop } = self;
+        let target = ascii_target(params, suffix);
+
+        let mut iterations = 0_u64;
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let mut randoms = [[0_u8; ATTACK_BYTES]; N];
+            rng.fill_alphanum(randoms.as_flattened_mut());
+            let prefixes = ascii_prefixes(isa, params, suffix.len(), target, &randoms);
+            let found = check_alphanum_batch(prefixes);
+
+            if let Some(ii) = found.iter().position(|&found| found) {
+                stop.store(true, Ordering::Relaxed);
+                let mut message = concat(prefixes[ii], &randoms[ii]);
+                message.extend_from_slice(suffix);
+                return Some(AsciiZeros { message, iterations: iterations + ii as u64 });
+            }
+            iterations += N as u64;
+        }
+    }
+}
+
+/// The state `prefix || random || suffix` requires after the prefix to hash
+/// to zero, with the random bytes still to be xored in
+fn ascii_target(params: &GlyphHashParams, suffix: &[u8]) -> Block {
+    let count = ATTACK_BYTES + suffix.len();
+    let mut target = ReverseGlyphHasher::new(params, Block::ZERO, count + 16);
+    target.update(suffix);
+    // The random bytes start the block after the prefix, so they are
+    // xored into the first bytes of the state.
+    return target.state();
+}
+
+/// The prefixes that give `prefix || random || suffix` a zero hash, for
+/// the `ascii_target` of a `suffix_len`-byte suffix
+#[inline(always)]
+fn ascii_prefixes<I: Isa, const N: usize>(
+    isa: I,
+    params: &GlyphHashParams,
+    suffix_len: usize,
+    target: Block,
+    randoms: &[[u8; ATTACK_BYTES]; N],
+) -> [Block; N] {
+    // Built from a word rather than bytes: byte stores followed by a
+    // block load stall on store forwarding.
+    let states = randoms.map(|random| {
+        let mut bytes = [0_u8; 8];
+        bytes[..ATTACK_BYTES].copy_from_slice(&random);
+        target ^ Block::from_u64s([u64::from_le_bytes(bytes), 0])
+    });
+    return single_prefix_batch_with(isa, params, ATTACK_BYTES + suffix_len, states);
+}
+
+impl<R: RandomSource> Kernel for AsciiSearch<'_, R> {
+    type Output = Option<AsciiZeros>;
+
+    #[inline(always)]
+    fn run<I: Isa>(self, isa: I) -> Option<AsciiZeros> {
+        if isa.backend().has_aes() {
+            return self.search::<I, ASCII_BATCH>(isa);
+        }
+        self.search::<I, ASCII_BATCH_BITSLICED>(isa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use srng::SRng;
+    use crate::test_util::{message, variants};
+
+    fn blocks<const N: usize>() -> [Block; N] {
+        core::array::from_fn(|ii| {
+            Block::from_u64s([(ii as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15), !(ii as u64) << 7])
+        })
+    }
+
+    #[test]
+    fn inverse_rounds() {
+        let key = Block::from_array(core::array::from_fn(|ii| ii as u8));
+        for block in blocks::<8>() {
+            assert_eq!(inv_aes_dec(block.aes_dec(key), key), block);
+            assert_eq!(inv_aes_decx4(aes_dec_rounds(block, 4)), block);
+        }
+    }
+
+    #[test]
+    fn check_alphanum_matches_bytes() {
+        let alphanum = *b"azAZ09bcYXyx1287";
+        assert!(check_alphanum(Block::from_array(alphanum)));
+        for byte in 0..=255 {
+            let mut bytes = alphanum;
+            bytes[byte as usize % 16] = byte;
+            assert_eq!(check_alphanum(Block::from_array(bytes)), byte.is_ascii_alphanumeric(), "{byte:#x}");
+        }
+    }
+
+    fn check_batch<const N: usize>() {
+        let input = blocks::<N>();
+        assert_eq!(inv_aes_decx4_batch(input), input.map(inv_aes_decx4));
+        for rounds in [1, 3, 4] {
+            let expected = input.map(|block| inv_aes_dec_rounds(block, rounds));
+            assert_eq!(inv_aes_dec_rounds_batch(isa::Soft, input, rounds), expected);
+            assert_eq!(inv_aes_dec_rounds_batch(isa::Detected, input, rounds), expected);
+        }
+        for params in variants() {
+            let expected = input.map(|target| single_prefix(&params, 21, target));
+            assert_eq!(single_prefix_batch(&params, 21, input), expected, "{params:?}");
+            assert_eq!(single_prefix_batch_with(isa::Soft, &params, 21, input), expected, "{params:?}");
+        }
+    }
+
+    #[test]
+    fn batches_match_scalar() {
+        // Whole and partial chunks of four blocks and of a bitsliced batch
+        check_batch::<1>();
+        check_batch::<5>();
+        check_batch::<8>();
+        check_batch::<70>();
+    }
+
+    #[test]
+    fn check_alphanum_batch_matches_scalar() {
+        let alphanum = *b"azAZ09bcYXyx1287";
+        let mut input = vec![Block::from_array(alphanum)];
+        for byte in 0..=255 {
+            let mut bytes = alphanum;
+            bytes[byte as usize % 16] = byte;
+            input.push(Block::from_array(bytes));
+        }
+        for chunk in input.chunks_exact(8) {
+            let chunk: [Block; 8] = chunk.try_into().unwrap();
+            assert_eq!(check_alphanum_batch(chunk), chunk.map(check_alphanum));
+        }
+    }
+
+    #[test]
+    fn single_prefix_reaches_target() {
+        let target = blocks::<2>()[1];
+        for params in variants() {
+            let prefix = single_prefix(&params, 21, target);
+            assert_eq!(params.absorb_block(params.initial_state(21 + 16), prefix), target, "{params:?}");
+        }
+    }
+
+    #[test]
+    fn backward_steps() {
+        let data = message(21);
+        let hash = ComputeGlyphHash(&data);
+        let params = GlyphHashParams::REFTERM;
+        let initial = params.initial_state(data.len());
+        assert_eq!(preimage_prefix_hash(&params, hash, &data), initial);
+
+        let first = Block::from_array(data[..16].try_into().unwrap());
+        let after_first = params.absorb_block(initial, first);
+        assert_eq!(invert_block(&params, after_first, &data[..16]), initial);
+        assert_eq!(preimage_prefix_hash(&params, hash, &data[16..]), after_first);
+        assert_eq!(invert_last(&params, &data[16..], hash), initial ^ first);
+    }
+
+    #[test]
+    fn attacks_match_compute_glyph_hash() {
+        let params = GlyphHashParams::REFTERM;
+        let target = blocks::<2>()[1];
+        for len in [0, 1, 15, 16, 17, 33] {
+            let data = message(len);
+            let hash = ComputeGlyphHash(&data);
+
+            if let Some(forgery) = padding_collision(&params, &data) {
+                assert_ne!(forgery, data);
+                assert_eq!(ComputeGlyphHash(&forgery), hash);
+            }
+
+            let prefix = prefix_collision(&params, &data);
+            assert_eq!(ComputeGlyphHash(&concat(prefix, &data)), hash);
+
+            let image = preimage(&params, &data, target);
+            assert!(image.ends_with(&data));
+            assert_eq!(ComputeGlyphHash(&image), target);
+
+            let forged = chosen_prefix(&params, &data, target);
+            assert!(forged.starts_with(&data));
+            assert_eq!(ComputeGlyphHash(&forged), target);
+        }
+        assert!(padding_collision(&params, &message(15)).is_none());
+        assert!(padding_collision(&params, &message(20)).is_some());
+
+        for len in 0..16 {
+            let hash = ComputeGlyphHash(&message(len));
+            let recovered = invert(&params, hash).unwrap();
+            assert_eq!(ComputeGlyphHash(&recovered), hash);
+        }
+    }
+
+    fn check_ascii_candidates<I: Isa, const N: usize>(isa: I) {
+        let suffix = b" Hello Casey!";
+        for params in variants() {
+            let target = ascii_target(&params, suffix);
+            let mut rng = Filler::new(<SRng>::from_seed(1));
+            let mut randoms = [[0_u8; ATTACK_BYTES]; N];
+            rng.fill_alphanum(randoms.as_flattened_mut());
+            let prefixes = ascii_prefixes(isa, &params, suffix.len(), target, &randoms);
+            for (prefix, random) in prefixes.into_iter().zip(randoms) {
+                let mut message = concat(prefix, &random);
+                message.extend_from_slice(suffix);
+                assert_eq!(params.hash(&message), Block::ZERO, "{params:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_candidates_hash_to_zero() {
+        // Finding an alphanumeric prefix takes billions of candidates, but
+        // every candidate hashes to zero
+        check_ascii_candidates::<_, ASCII_BATCH>(isa::Detected);
+        check_ascii_candidates::<_, ASCII_BATCH_BITSLICED>(isa::Soft);
+    }
+
+    #[test]
+    fn ascii_search_stops() {
+        let stop = AtomicBool::new(true);
+        let mut rng = Filler::new(<SRng>::from_seed(1));
+        assert!(find_ascii_zeros(&GlyphHashParams::REFTERM, b"!", &mut rng, &stop).is_none());
+    }
+}
@@ -0,0 +1,212 @@
+//! `ComputeGlyphHash` run backwards, consuming the message from the end
+
+use simd_aes::Block;
+use super::GlyphHashParams;
+
+/// Backward glyph hasher: starts from a target hash and consumes the message
+/// from the end, yielding the state the remaining prefix has to produce.
+///
+/// Every byte consumed by the hash is xored into the state, so bytes can be
+/// xored back out one at a time; the inverse AES rounds are applied when
+/// the hasher steps into the block before the current one.
+#[derive(Clone, Copy, Debug)]
+pub struct ReverseGlyphHasher {
+    /// The hash variant
+    params: GlyphHashParams,
+    /// State at the start of the current block, xored with the bytes of
+    /// that block that were not consumed yet
+    state: Block,
+    /// Number of message bytes not consumed yet
+    remaining: usize,
+    /// Total message length
+    len: usize,
+}
+
+impl ReverseGlyphHasher {
+    /// Start from the final `hash` of a message of `len` bytes
+    pub fn new(params: &GlyphHashParams, hash: Block, len: usize) -> Self {
+        // The tail bytes are not known yet, only the padding around them.
+        let padding = params.tail_block(&[0; 16][..len % 16]);
+        let state = match padding {
+            Some(padding) => params.inv_tail_rounds(hash) ^ padding,
+            None => hash,
+        };
+        Self {
+            params: *params,
+            state,
+            remaining: len,
+            len,
+        }
+    }
+
+    /// Start from the chaining `state` after the first `prefix_len` bytes of
+    /// a message of `len` bytes, i.e. the value at the end of a full block.
+    ///
+    /// # Panics
+    /// If `prefix_len` is not a multiple of 16 or exceeds `len`.
+    pub fn with_state(params: &GlyphHashParams, state: Block, prefix_len: usize, len: usize) -> Self {
+        assert!(prefix_len.is_multiple_of(16), "prefix length must end on a block boundary");
+        assert!(prefix_len <= len, "prefix length exceeds message length");
+        Self {
+            params: *params,
+            state,
+            remaining: prefix_len,
+            len,
+        }
+    }
+
+    /// Total message length
+    pub fn message_len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of message bytes not consumed yet, i.e. the prefix length
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Whether the consumed suffix starts on a block boundary
+    pub fn is_aligned(&self) -> bool {
+        self.remaining.is_multiple_of(16)
+    }
+
+    /// The state required from the remaining prefix.
+    ///
+    /// When aligned, this is the chaining value the prefix has to produce.
+    /// Otherwise it is the chaining value before the current block, xored
+    /// with the block bytes that were not consumed yet.
+    pub fn state(&self) -> Block {
+        self.state
+    }
+
+    /// Consume the chunk of the message right before the already consumed
+    /// suffix. Chunks are fed in reverse order, each chunk in forward order.
+    ///
+    /// # Panics
+    /// If the chunk is longer than the remaining prefix.
+    #[inline]
+    pub fn update(&mut self, mut data: &[u8]) {
+        assert!(data.len() <= self.remaining, "glyph hash input exceeds message length");
+        while !data.is_empty() {
+            let end = match self.remaining % 16 {
+                0 => {
+                    self.state = self.params.inv_block_rounds(self.state);
+                    16
+                }
+                offset => offset,
+            };
+            let take = end.min(data.len());
+            let (rest, chunk) = data.split_at(data.len() - take);
+            let mut block = [0_u8; 16];
+            block[end - take..end].copy_from_slice(chunk);
+            self.state ^= Block::from_array(block);
+            self.remaining -= take;
+            data = rest;
+        }
+    }
+
+    /// The block that takes the chaining value `chain` to the required state.
+    ///
+    /// If some bytes of the current block were consumed already, the
+    /// matching bytes of the result are zero when `chain` is consistent.
+    #[inline]
+    pub fn block_from(&self, chain: Block) -> Block {
+        if self.is_aligned() && self.remaining > 0 {
+            return self.params.inv_block_rounds(self.state) ^ chain;
+        }
+        self.state ^ chain
+    }
+
+    /// The first block of the message, given everything after it.
+    ///
+    /// Its first `remaining` bytes are the prefix; the following bytes are
+    /// zero if the consumed suffix is consistent with the target hash.
+    ///
+    /// # Panics
+    /// If more than 16 bytes remain.
+    #[inline]
+    pub fn prefix_block(&self) -> Block {
+        assert!(self.remaining <= 16, "more than one block remaining");
+        self.block_from(self.params.initial_state(self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GlyphHasher;
+    use crate::test_util::{message, variants};
+
+    const LENGTHS: [usize; 9] = [0, 1, 15, 16, 17, 31, 32, 33, 48];
+
+    /// The chaining values after each full block of `data`, starting with
+    /// the initial state
+    fn chain(params: &GlyphHashParams, data: &[u8]) -> Vec<Block> {
+        let mut state = params.initial_state(data.len());
+        let mut chain = vec![state];
+        for block in data.chunks_exact(16) {
+            state = params.absorb_block(state, Block::from_array(block.try_into().unwrap()));
+            chain.push(state);
+        }
+        chain
+    }
+
+    #[test]
+    fn rewinds_forward_hash() {
+        for params in variants() {
+            for len in LENGTHS {
+                let data = message(len);
+                let chain = chain(&params, &data);
+                let mut forward = GlyphHasher::with_len(&params, len);
+                forward.update(&data);
+                let hash = forward.finalize();
+
+                // Rewind from the hash in 5-byte chunks, checking each block
+                // boundary and the first block
+                let mut reverse = ReverseGlyphHasher::new(&params, hash, len);
+                let mut end = len;
+                loop {
+                    if reverse.is_aligned() {
+                        assert_eq!(reverse.state(), chain[end / 16], "{params:?}, {len} bytes at {end}");
+                    }
+                    if end <= 16 {
+                        let mut first = [0_u8; 16];
+                        first[..end].copy_from_slice(&data[..end]);
+                        assert_eq!(reverse.prefix_block(), Block::from_array(first), "{params:?}, {len} bytes at {end}");
+                    }
+                    if end == 0 {
+                        break;
+                    }
+                    let start = end.saturating_sub(5);
+                    reverse.update(&data[start..end]);
+                    end = start;
+                    assert_eq!(reverse.remaining(), end);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rewinds_from_chaining_state() {
+        for params in variants() {
+            for len in LENGTHS {
+                let data = message(len);
+                let chain = chain(&params, &data);
+                for blocks in 1..chain.len() {
+                    let prefix_len = blocks * 16;
+                    let reverse = ReverseGlyphHasher::with_state(&params, chain[blocks], prefix_len, len);
+                    assert_eq!(reverse.message_len(), len);
+                    let block = Block::from_array(data[prefix_len - 16..prefix_len].try_into().unwrap());
+                    assert_eq!(reverse.block_from(chain[blocks - 1]), block);
+
+                    let mut rewound = reverse;
+                    rewound.update(&data[16..prefix_len]);
+                    assert_eq!(rewound.state(), chain[1]);
+                    assert_eq!(rewound.prefix_block(), Block::from_array(data[..16].try_into().unwrap()));
+                    rewound.update(&data[..16]);
+                    assert_eq!(rewound.state(), chain[0]);
+                }
+            }
+        }
+    }
+}
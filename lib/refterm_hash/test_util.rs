@@ -0,0 +1,29 @@
+//! Fixtures shared by the tests of this crate
+
+use simd_aes::Block;
+use super::{DEFAULT_SEED, GlyphHashParams, Lane, LengthEncoding, TailPadding};
+
+/// A `len`-byte message without zero bytes in its first 145 bytes
+pub fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|ii| (ii * 37 + 11) as u8).collect()
+}
+
+/// Every length encoding and tail padding, with refterm's seed and rounds
+/// and with a zero seed and different block and tail rounds. The first
+/// variant is `GlyphHashParams::REFTERM`.
+pub fn variants() -> impl Iterator<Item = GlyphHashParams> {
+    let lengths = [
+        LengthEncoding::Le(Lane::Low),
+        LengthEncoding::Le(Lane::High),
+        LengthEncoding::Be(Lane::Low),
+        LengthEncoding::Be(Lane::High),
+        LengthEncoding::None,
+    ];
+    let paddings = [TailPadding::Zeros, TailPadding::ZerosSkipEmpty, TailPadding::Fill(b' '), TailPadding::Marker(0x80)];
+    let rounds = [(DEFAULT_SEED, 4, 4), (Block::ZERO, 3, 5)];
+    rounds.into_iter().flat_map(move |(seed, block_rounds, tail_rounds)| {
+        lengths.into_iter().flat_map(move |length| {
+            paddings.into_iter().map(move |padding| GlyphHashParams { seed, block_rounds, tail_rounds, length, padding })
+        })
+    })
+}
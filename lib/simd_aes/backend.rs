@@ -0,0 +1,191 @@
+//! Runtime detection of the AES instructions available on this CPU.
+//!
+//! Without `-Ctarget-feature=+aes` the `SimdAes` rounds check the detected
+//! backend on every operation. Hot loops should run through `dispatch`
+//! instead, which runs a kernel generic over the `Isa` marker of the
+//! detected backend inside a function compiled with that backend's target
+//! features.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+use super::isa::{self, Isa};
+
+/// An implementation of the AES rounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Backend {
+    /// Portable software rounds
+    Soft = 1,
+    /// x86_64 AES-NI, one block per instruction
+    AesNi = 2,
+    /// x86_64 VAES with AVX2, two blocks per instruction
+    Vaes256 = 3,
+    /// x86_64 VAES with AVX-512, four blocks per instruction
+    Vaes512 = 4,
+    /// aarch64 cryptographic extension
+    ArmCrypto = 5,
+}
+
+impl Backend {
+    /// Short name, e.g. for benchmark output
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Soft => "soft",
+            Backend::AesNi => "aes-ni",
+            Backend::Vaes256 => "vaes-256",
+            Backend::Vaes512 => "vaes-512",
+            Backend::ArmCrypto => "arm-crypto",
+        }
+    }
+
+    /// Whether single-block hardware AES instructions are available
+    #[inline]
+    pub fn has_aes(self) -> bool {
+        self != Backend::Soft
+    }
+
+    /// Query the CPU for the best available backend
+    pub fn detect() -> Backend {
+        #[cfg(target_arch = "x86_64")]
+        {
+            // Every CPU with AES-NI has SSE4.1, which the step functions use
+            if !std::arch::is_x86_feature_detected!("aes")
+                || !std::arch::is_x86_feature_detected!("sse4.1")
+            {
+                return Backend::Soft;
+            }
+            if std::arch::is_x86_feature_detected!("vaes") {
+                if std::arch::is_x86_feature_detected!("avx512f") {
+                    return Backend::Vaes512;
+                }
+                if std::arch::is_x86_feature_detected!("avx2") {
+                    return Backend::Vaes256;
+                }
+            }
+            return Backend::AesNi;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("aes") {
+                return Backend::ArmCrypto;
+            }
+            return Backend::Soft;
+        }
+        #[allow(unreachable_code)]
+        Backend::Soft
+    }
+
+    fn from_u8(value: u8) -> Option<Backend> {
+        match value {
+            1 => Some(Backend::Soft),
+            2 => Some(Backend::AesNi),
+            3 => Some(Backend::Vaes256),
+            4 => Some(Backend::Vaes512),
+            5 => Some(Backend::ArmCrypto),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Detected backend, 0 until the first query
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// The best backend available on this CPU, detected once
+#[inline]
+pub fn backend() -> Backend {
+    match Backend::from_u8(BACKEND.load(Ordering::Relaxed)) {
+        Some(backend) => backend,
+        None => {
+            let backend = Backend::detect();
+            BACKEND.store(backend as u8, Ordering::Relaxed);
+            backend
+        }
+    }
+}
+
+/// Whether the hardware rounds can be used, known at compile time when the
+/// target enables AES
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+pub(crate) fn has_hardware_aes() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    let enabled = cfg!(all(target_feature = "aes", target_feature = "sse4.1"));
+    #[cfg(target_arch = "aarch64")]
+    let enabled = cfg!(target_feature = "aes");
+    enabled || backend().has_aes()
+}
+
+/// Whether 256-bit VAES can be used
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) fn has_vaes256() -> bool {
+    cfg!(all(target_feature = "vaes", target_feature = "avx2"))
+        || matches!(backend(), Backend::Vaes256 | Backend::Vaes512)
+}
+
+/// Whether 512-bit VAES can be used
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) fn has_vaes512() -> bool {
+    cfg!(all(target_feature = "vaes", target_feature = "avx512f"))
+        || backend() == Backend::Vaes512
+}
+
+/// A computation to run with the detected target features enabled.
+///
+/// `run` gets the marker of the backend and should do its AES rounds
+/// through it. `run` and everything it calls in the hot loop should be
+/// `#[inline(always)]`, so that they are compiled into the
+/// `#[target_feature]` function of that backend; an out-of-line call loses
+/// the features and the intrinsics are called instead of inlined.
+pub trait Kernel {
+    type Output;
+    fn run<I: Isa>(self, isa: I) -> Self::Output;
+}
+
+/// Run `kernel` compiled with the target features of the detected backend
+#[inline]
+pub fn dispatch<K: Kernel>(kernel: K) -> K::Output {
+    match backend() {
+        // SAFETY: the backend was detected, so the features are available.
+        #[cfg(target_arch = "x86_64")]
+        Backend::AesNi => unsafe { run_aesni(kernel) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Vaes256 => unsafe { run_vaes256(kernel) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Vaes512 => unsafe { run_vaes512(kernel) },
+        #[cfg(target_arch = "aarch64")]
+        Backend::ArmCrypto => unsafe { run_arm_crypto(kernel) },
+        _ => kernel.run(isa::Soft),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,sse4.1")]
+unsafe fn run_aesni<K: Kernel>(kernel: K) -> K::Output {
+    kernel.run(isa::AesNi::new_unchecked())
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,avx2,vaes")]
+unsafe fn run_vaes256<K: Kernel>(kernel: K) -> K::Output {
+    kernel.run(isa::Vaes256::new_unchecked())
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,avx2,avx512f,vaes")]
+unsafe fn run_vaes512<K: Kernel>(kernel: K) -> K::Output {
+    kernel.run(isa::Vaes512::new_unchecked())
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "aes")]
+unsafe fn run_arm_crypto<K: Kernel>(kernel: K) -> K::Output {
+    kernel.run(isa::ArmCrypto::new_unchecked())
+}
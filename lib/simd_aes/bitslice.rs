@@ -0,0 +1,541 @@
+//! Bitsliced AES rounds on many blocks at once, for CPUs without AES
+//! instructions.
+//!
+//! `Bitsliced<W>` holds one block per bit of the word type `W`: 32 blocks
+//! for `u32`, 64 for `u64`. Word `bits[j][i]` holds bit `j` of byte `i` of
+//! every block, so every step is a fixed sequence of word-wide AND/XOR/NOT.
+//! The S-box is the 113-gate circuit of Boyar and Peralta, and blocks are
+//! transposed in and out with swapmoves; there are no tables and no
+//! data-dependent branches.
+
+use core::ops::{BitAnd, BitOrAssign, BitXor, BitXorAssign, Not, Shl, Shr};
+use super::{Block, SimdAes};
+use super::gf256::mul;
+
+/// Low halves of every `2 * width` bits, for `width` 1, 2, 4, ..., 32
+const SWAP_MASKS: [u64; 6] = [
+    0x5555555555555555,
+    0x3333333333333333,
+    0x0f0f0f0f0f0f0f0f,
+    0x00ff00ff00ff00ff,
+    0x0000ffff0000ffff,
+    0x00000000ffffffff,
+];
+
+/// A word holding one bit of as many blocks as it has bits
+pub trait BitWord:
+    Copy
+    + Default
+    + PartialEq
+    + BitXor<Output = Self>
+    + BitAnd<Output = Self>
+    + Not<Output = Self>
+    + BitOrAssign
+    + Shl<usize, Output = Self>
+    + Shr<usize, Output = Self>
+    + From<bool>
+{
+    /// Number of blocks, the number of bits in the word
+    const BLOCKS: usize;
+
+    /// The low `BLOCKS` bits of `value`
+    fn from_u128(value: u128) -> Self;
+
+    /// The word zero-extended to 128 bits
+    fn to_u128(self) -> u128;
+}
+
+impl BitWord for u32 {
+    const BLOCKS: usize = 32;
+
+    fn from_u128(value: u128) -> Self {
+        value as u32
+    }
+
+    fn to_u128(self) -> u128 {
+        self as u128
+    }
+}
+
+impl BitWord for u64 {
+    const BLOCKS: usize = 64;
+
+    fn from_u128(value: u128) -> Self {
+        value as u64
+    }
+
+    fn to_u128(self) -> u128 {
+        self as u128
+    }
+}
+
+/// One bit of each of the 16 bytes of the state
+type Plane<W> = [W; 16];
+
+/// The whole state: 8 planes, least significant bit first
+type Planes<W> = [Plane<W>; 8];
+
+/// `W::BLOCKS` blocks in bitsliced form
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bitsliced<W> {
+    bits: Planes<W>,
+}
+
+impl<W: BitWord> Default for Bitsliced<W> {
+    fn default() -> Self {
+        Bitsliced { bits: [[W::default(); 16]; 8] }
+    }
+}
+
+impl<W: BitWord> Bitsliced<W> {
+    /// Number of blocks
+    pub const BLOCKS: usize = W::BLOCKS;
+
+    /// The same block in every position
+    pub fn splat(block: Block) -> Self {
+        let bytes = block.to_array();
+        let bits = core::array::from_fn(|bit| {
+            core::array::from_fn(|byte| match (bytes[byte] >> bit) & 1 {
+                0 => W::default(),
+                _ => !W::default(),
+            })
+        });
+        Bitsliced { bits }
+    }
+
+    /// Transpose up to `BLOCKS` blocks into bitsliced form; missing blocks
+    /// are zero
+    pub fn pack(blocks: &[Block]) -> Self {
+        assert!(blocks.len() <= W::BLOCKS, "too many blocks");
+        let mut sliced = Self::default();
+        // Bits `chunk * BLOCKS..` of every block make a square bit matrix
+        for chunk in 0..128 / W::BLOCKS {
+            let mut rows = [W::default(); 64];
+            for (row, block) in rows.iter_mut().zip(blocks) {
+                *row = W::from_u128(u128::from_le_bytes(block.to_array()) >> (chunk * W::BLOCKS));
+            }
+            transpose(&mut rows[..W::BLOCKS]);
+            for (row, &word) in rows[..W::BLOCKS].iter().enumerate() {
+                let bit = chunk * W::BLOCKS + row;
+                sliced.bits[bit % 8][bit / 8] = word;
+            }
+        }
+        sliced
+    }
+
+    /// The block at `index`
+    pub fn block(&self, index: usize) -> Block {
+        assert!(index < W::BLOCKS, "block index out of range");
+        let mut blocks = [Block::ZERO; 64];
+        self.unpack(&mut blocks[..W::BLOCKS]);
+        blocks[index]
+    }
+
+    /// Transpose back into `blocks`, at most `BLOCKS` of them
+    pub fn unpack(&self, blocks: &mut [Block]) {
+        assert!(blocks.len() <= W::BLOCKS, "too many blocks");
+        let mut values = [0_u128; 64];
+        for chunk in 0..128 / W::BLOCKS {
+            let mut rows = [W::default(); 64];
+            for (row, word) in rows[..W::BLOCKS].iter_mut().enumerate() {
+                let bit = chunk * W::BLOCKS + row;
+                *word = self.bits[bit % 8][bit / 8];
+            }
+            transpose(&mut rows[..W::BLOCKS]);
+            for (value, row) in values.iter_mut().zip(&rows[..W::BLOCKS]) {
+                *value |= row.to_u128() << (chunk * W::BLOCKS);
+            }
+        }
+        for (block, value) in blocks.iter_mut().zip(values) {
+            *block = Block::from_array(value.to_le_bytes());
+        }
+    }
+}
+
+/// Transpose the square bit matrix `rows`, moving bit `j` of row `i` to bit
+/// `i` of row `j`, by swapping ever smaller blocks of bits between rows
+#[inline]
+fn transpose<W: BitWord>(rows: &mut [W]) {
+    let mut width = W::BLOCKS / 2;
+    while width > 0 {
+        let mask = W::from_u128(SWAP_MASKS[width.trailing_zeros() as usize] as u128);
+        for pairs in rows.chunks_exact_mut(2 * width) {
+            // The high halves of each row trade places with the low halves
+            // of the row `width` below it
+            let (low, high) = pairs.split_at_mut(width);
+            for (low, high) in low.iter_mut().zip(high) {
+                let swap = ((*low >> width) ^ *high) & mask;
+                *low = *low ^ (swap << width);
+                *high = *high ^ swap;
+            }
+        }
+        width /= 2;
+    }
+}
+
+#[inline]
+fn xor<W: BitWord>(a: Plane<W>, b: Plane<W>) -> Plane<W> {
+    core::array::from_fn(|ii| a[ii] ^ b[ii])
+}
+
+#[inline]
+fn not<W: BitWord>(a: Plane<W>) -> Plane<W> {
+    a.map(|word| !word)
+}
+
+/// The columns of multiplication by `factor`, as a linear map on bits
+const fn mul_columns(factor: u8) -> [u8; 8] {
+    let mut columns = [0; 8];
+    let mut bit = 0;
+    while bit < 8 {
+        columns[bit] = mul(1 << bit, factor);
+        bit += 1;
+    }
+    columns
+}
+
+/// The columns of the xor of the byte rotated left by each of `rotations`
+const fn rotation_columns(rotations: &[u32]) -> [u8; 8] {
+    let mut columns = [0; 8];
+    let mut bit = 0;
+    while bit < 8 {
+        let mut ii = 0;
+        while ii < rotations.len() {
+            columns[bit] ^= (1_u8 << bit).rotate_left(rotations[ii]);
+            ii += 1;
+        }
+        bit += 1;
+    }
+    columns
+}
+
+/// Apply the linear map taking bit `i` to `columns[i]` to every byte
+#[inline]
+fn linear<W: BitWord>(a: &Planes<W>, columns: &[u8; 8]) -> Planes<W> {
+    let mut out = [[W::default(); 16]; 8];
+    for (input, &column) in a.iter().zip(columns) {
+        for (bit, out) in out.iter_mut().enumerate() {
+            if (column >> bit) & 1 != 0 {
+                *out = xor(*out, *input);
+            }
+        }
+    }
+    out
+}
+
+/// Xor the constant `value` into every byte
+#[inline]
+fn add_constant<W: BitWord>(mut a: Planes<W>, value: u8) -> Planes<W> {
+    for (bit, plane) in a.iter_mut().enumerate() {
+        if (value >> bit) & 1 != 0 {
+            *plane = not(*plane);
+        }
+    }
+    a
+}
+
+/// The S-box without its affine constant `0x63` on one byte of every
+/// block, least significant bit first: the 113-gate circuit of Boyar and
+/// Peralta, "A depth-16 circuit for the AES S-box", with its four XNORs as
+/// XORs
+#[inline]
+fn sbox_circuit<W: BitWord>(input: [W; 8]) -> [W; 8] {
+    let [u7, u6, u5, u4, u3, u2, u1, u0] = input;
+
+    // Top linear transformation
+    let y14 = u3 ^ u5;
+    let y13 = u0 ^ u6;
+    let y9 = u0 ^ u3;
+    let y8 = u0 ^ u5;
+    let t0 = u1 ^ u2;
+    let y1 = t0 ^ u7;
+    let y4 = y1 ^ u3;
+    let y12 = y13 ^ y14;
+    let y2 = y1 ^ u0;
+    let y5 = y1 ^ u6;
+    let y3 = y5 ^ y8;
+    let t1 = u4 ^ y12;
+    let y15 = t1 ^ u5;
+    let y20 = t1 ^ u1;
+    let y6 = y15 ^ u7;
+    let y10 = y15 ^ t0;
+    let y11 = y20 ^ y9;
+    let y7 = u7 ^ y11;
+    let y17 = y10 ^ y11;
+    let y19 = y10 ^ y8;
+    let y16 = t0 ^ y11;
+    let y21 = y13 ^ y16;
+    let y18 = u0 ^ y16;
+
+    // Shared nonlinear middle: the inverse in GF(2^8) as a tower field
+    let t2 = y12 & y15;
+    let t3 = y3 & y6;
+    let t4 = t3 ^ t2;
+    let t5 = y4 & u7;
+    let t6 = t5 ^ t2;
+    let t7 = y13 & y16;
+    let t8 = y5 & y1;
+    let t9 = t8 ^ t7;
+    let t10 = y2 & y7;
+    let t11 = t10 ^ t7;
+    let t12 = y9 & y11;
+    let t13 = y14 & y17;
+    let t14 = t13 ^ t12;
+    let t15 = y8 & y10;
+    let t16 = t15 ^ t12;
+    let t17 = t4 ^ t14;
+    let t18 = t6 ^ t16;
+    let t19 = t9 ^ t14;
+    let t20 = t11 ^ t16;
+    let t21 = t17 ^ y20;
+    let t22 = t18 ^ y19;
+    let t23 = t19 ^ y21;
+    let t24 = t20 ^ y18;
+
+    let t25 = t21 ^ t22;
+    let t26 = t21 & t23;
+    let t27 = t24 ^ t26;
+    let t28 = t25 & t27;
+    let t29 = t28 ^ t22;
+    let t30 = t23 ^ t24;
+    let t31 = t22 ^ t26;
+    let t32 = t31 & t30;
+    let t33 = t32 ^ t24;
+    let t34 = t23 ^ t33;
+    let t35 = t27 ^ t33;
+    let t36 = t24 & t35;
+    let t37 = t36 ^ t34;
+    let t38 = t27 ^ t36;
+    let t39 = t29 & t38;
+    let t40 = t25 ^ t39;
+
+    let t41 = t40 ^ t37;
+    let t42 = t29 ^ t33;
+    let t43 = t29 ^ t40;
+    let t44 = t33 ^ t37;
+    let t45 = t42 ^ t41;
+    let z0 = t44 & y15;
+    let z1 = t37 & y6;
+    let z2 = t33 & u7;
+    let z3 = t43 & y16;
+    let z4 = t40 & y1;
+    let z5 = t29 & y7;
+    let z6 = t42 & y11;
+    let z7 = t45 & y17;
+    let z8 = t41 & y10;
+    let z9 = t44 & y12;
+    let z10 = t37 & y3;
+    let z11 = t33 & y4;
+    let z12 = t43 & y13;
+    let z13 = t40 & y5;
+    let z14 = t29 & y2;
+    let z15 = t42 & y9;
+    let z16 = t45 & y14;
+    let z17 = t41 & y8;
+
+    // Bottom linear transformation
+    let t46 = z15 ^ z16;
+    let t47 = z10 ^ z11;
+    let t48 = z5 ^ z13;
+    let t49 = z9 ^ z10;
+    let t50 = z2 ^ z12;
+    let t51 = z2 ^ z5;
+    let t52 = z7 ^ z8;
+    let t53 = z0 ^ z3;
+    let t54 = z6 ^ z7;
+    let t55 = z16 ^ z17;
+    let t56 = z12 ^ t48;
+    let t57 = t50 ^ t53;
+    let t58 = z4 ^ t46;
+    let t59 = z3 ^ t54;
+    let t60 = t46 ^ t57;
+    let t61 = z14 ^ t57;
+    let t62 = t52 ^ t58;
+    let t63 = t49 ^ t58;
+    let t64 = z4 ^ t59;
+    let t65 = t61 ^ t62;
+    let t66 = z1 ^ t63;
+    let s0 = t59 ^ t63;
+    let s6 = t56 ^ t62;
+    let s7 = t48 ^ t60;
+    let t67 = t64 ^ t65;
+    let s3 = t53 ^ t66;
+    let s4 = t51 ^ t66;
+    let s5 = t47 ^ t65;
+    let s1 = t64 ^ s3;
+    let s2 = t55 ^ t67;
+
+    [s7, s6, s5, s4, s3, s2, s1, s0]
+}
+
+/// `sbox_circuit` on every byte
+#[inline]
+fn sbox_bytes<W: BitWord>(a: &Planes<W>) -> Planes<W> {
+    let mut out = *a;
+    for byte in 0..16 {
+        let bits = sbox_circuit(core::array::from_fn(|bit| a[bit][byte]));
+        for (plane, bit) in out.iter_mut().zip(bits) {
+            plane[byte] = bit;
+        }
+    }
+    out
+}
+
+/// Permute the bytes of every plane: `out[i] = in[index(i)]`
+#[inline]
+fn permute<W: BitWord>(a: &Planes<W>, index: impl Fn(usize) -> usize) -> Planes<W> {
+    a.map(|plane| core::array::from_fn(|ii| plane[index(ii)]))
+}
+
+/// Rotate every column up by `rows`, so row `r` gets row `r + rows`
+#[inline]
+fn rotate_columns<W: BitWord>(a: &Planes<W>, rows: usize) -> Planes<W> {
+    permute(a, |ii| (ii & !3) | ((ii + rows) & 3))
+}
+
+#[inline]
+fn add<W: BitWord>(a: &Planes<W>, b: &Planes<W>) -> Planes<W> {
+    core::array::from_fn(|bit| xor(a[bit], b[bit]))
+}
+
+const INV_AFFINE: [u8; 8] = rotation_columns(&[1, 3, 6]);
+const XTIME: [u8; 8] = mul_columns(2);
+const MUL9: [u8; 8] = mul_columns(9);
+const MUL11: [u8; 8] = mul_columns(11);
+const MUL13: [u8; 8] = mul_columns(13);
+const MUL14: [u8; 8] = mul_columns(14);
+
+impl<W: BitWord> BitXor for Bitsliced<W> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self {
+        Bitsliced { bits: add(&self.bits, &rhs.bits) }
+    }
+}
+
+impl<W: BitWord> BitXorAssign for Bitsliced<W> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = *self ^ rhs;
+    }
+}
+
+impl<W: BitWord> SimdAes for Bitsliced<W> {
+    #[inline]
+    fn sub_bytes(self) -> Self {
+        Bitsliced { bits: add_constant(sbox_bytes(&self.bits), 0x63) }
+    }
+
+    #[inline]
+    fn inv_sub_bytes(self) -> Self {
+        // The inverse in GF(2^8) is the S-box with its affine map undone
+        let inverse = add_constant(linear(&self.bits, &INV_AFFINE), 0x05);
+        let bits = linear(&sbox_bytes(&inverse), &INV_AFFINE);
+        Bitsliced { bits }
+    }
+
+    #[inline]
+    fn shift_rows(self) -> Self {
+        Bitsliced { bits: permute(&self.bits, |ii| (ii + 4 * (ii % 4)) % 16) }
+    }
+
+    #[inline]
+    fn inv_shift_rows(self) -> Self {
+        Bitsliced { bits: permute(&self.bits, |ii| (ii + 16 - 4 * (ii % 4)) % 16) }
+    }
+
+    #[inline]
+    fn mix_columns(self) -> Self {
+        let a1 = rotate_columns(&self.bits, 1);
+        let a2 = rotate_columns(&self.bits, 2);
+        let a3 = rotate_columns(&self.bits, 3);
+        let bits = linear(&add(&self.bits, &a1), &XTIME);
+        Bitsliced { bits: add(&add(&bits, &a1), &add(&a2, &a3)) }
+    }
+
+    #[inline]
+    fn inv_mix_columns(self) -> Self {
+        let a1 = rotate_columns(&self.bits, 1);
+        let a2 = rotate_columns(&self.bits, 2);
+        let a3 = rotate_columns(&self.bits, 3);
+        let bits = add(&linear(&self.bits, &MUL14), &linear(&a1, &MUL11));
+        Bitsliced { bits: add(&bits, &add(&linear(&a2, &MUL13), &linear(&a3, &MUL9))) }
+    }
+
+    #[inline]
+    fn aes_enc(self, key: Self) -> Self {
+        self.sub_bytes().shift_rows().mix_columns() ^ key
+    }
+
+    #[inline]
+    fn aes_enc_last(self, key: Self) -> Self {
+        self.sub_bytes().shift_rows() ^ key
+    }
+
+    #[inline]
+    fn aes_dec(self, key: Self) -> Self {
+        self.inv_shift_rows().inv_sub_bytes().inv_mix_columns() ^ key
+    }
+
+    #[inline]
+    fn aes_dec_last(self, key: Self) -> Self {
+        self.inv_shift_rows().inv_sub_bytes() ^ key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soft;
+    use crate::test_util::blocks;
+
+    fn check<W: BitWord>() {
+        let inputs: Vec<Block> = blocks(W::BLOCKS).collect();
+        let keys: Vec<Block> = blocks(2 * W::BLOCKS).collect();
+        let state = Bitsliced::<W>::pack(&inputs);
+        let key = Bitsliced::<W>::pack(&keys[W::BLOCKS..]);
+
+        type Round<T> = fn(T, T) -> T;
+        let rounds: [(Round<Bitsliced<W>>, Round<Block>); 4] = [
+            (SimdAes::aes_enc, soft::aes_enc),
+            (SimdAes::aes_enc_last, soft::aes_enc_last),
+            (SimdAes::aes_dec, soft::aes_dec),
+            (SimdAes::aes_dec_last, soft::aes_dec_last),
+        ];
+        for (sliced, single) in rounds {
+            let out = sliced(state, key);
+            for (index, &block) in inputs.iter().enumerate() {
+                assert_eq!(out.block(index), single(block, keys[W::BLOCKS + index]));
+            }
+        }
+
+        let mut unpacked = vec![Block::ZERO; W::BLOCKS];
+        state.unpack(&mut unpacked);
+        assert_eq!(unpacked, inputs);
+        assert_eq!(Bitsliced::<W>::splat(inputs[3]).block(W::BLOCKS - 1), inputs[3]);
+    }
+
+    /// Every byte value through the S-box and its inverse
+    #[test]
+    fn sbox_circuit_matches_tables() {
+        let inputs: Vec<Block> =
+            (0..16).map(|ii| Block::from_array(core::array::from_fn(|byte| (16 * ii + byte) as u8))).collect();
+        let state = Bitsliced::<u32>::pack(&inputs);
+        let (sub, inv) = (state.sub_bytes(), state.inv_sub_bytes());
+        for (index, &block) in inputs.iter().enumerate() {
+            assert_eq!(sub.block(index), soft::sub_bytes(block));
+            assert_eq!(inv.block(index), soft::inv_sub_bytes(block));
+        }
+    }
+
+    #[test]
+    fn u32_matches_soft() {
+        check::<u32>();
+    }
+
+    #[test]
+    fn u64_matches_soft() {
+        check::<u64>();
+    }
+}
@@ -0,0 +1,191 @@
+//! A 128-bit block backed by the platform's vector register type, so the
+//! crate builds on stable Rust without `portable_simd`.
+
+use core::fmt;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign};
+
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::{__m128i, _mm_and_si128, _mm_or_si128, _mm_xor_si128};
+
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::{uint8x16_t, vandq_u8, veorq_u8, vorrq_u8};
+
+#[cfg(target_arch = "x86_64")]
+type Inner = __m128i;
+#[cfg(target_arch = "aarch64")]
+type Inner = uint8x16_t;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+type Inner = [u8; 16];
+
+/// 16 bytes, e.g. one AES state
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct Block(pub(crate) Inner);
+
+impl Block {
+    /// All-zero block
+    pub const ZERO: Block = Block::splat(0);
+
+    /// Construct a block from its bytes
+    #[inline]
+    // Without vector registers `Inner` is the array itself
+    #[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), allow(clippy::useless_transmute))]
+    pub const fn from_array(bytes: [u8; 16]) -> Self {
+        // SAFETY: `Inner` is 16 bytes of plain data.
+        Block(unsafe { core::mem::transmute::<[u8; 16], Inner>(bytes) })
+    }
+
+    /// The bytes of the block
+    #[inline]
+    // Without vector registers `Inner` is the array itself
+    #[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), allow(clippy::useless_transmute))]
+    pub const fn to_array(self) -> [u8; 16] {
+        // SAFETY: `Inner` is 16 bytes of plain data.
+        unsafe { core::mem::transmute::<Inner, [u8; 16]>(self.0) }
+    }
+
+    /// Block with every byte set to `byte`
+    #[inline]
+    pub const fn splat(byte: u8) -> Self {
+        Self::from_array([byte; 16])
+    }
+
+    /// Construct a block from two native-endian `u64` lanes
+    #[inline]
+    pub const fn from_u64s(lanes: [u64; 2]) -> Self {
+        // SAFETY: `Inner` is 16 bytes of plain data.
+        Block(unsafe { core::mem::transmute::<[u64; 2], Inner>(lanes) })
+    }
+
+    /// The block as two native-endian `u64` lanes
+    #[inline]
+    pub const fn to_u64s(self) -> [u64; 2] {
+        // SAFETY: `Inner` is 16 bytes of plain data.
+        unsafe { core::mem::transmute::<Inner, [u64; 2]>(self.0) }
+    }
+}
+
+impl Default for Block {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_u64s() == other.to_u64s()
+    }
+}
+
+impl Eq for Block {}
+
+impl core::hash::Hash for Block {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.to_array().hash(state)
+    }
+}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_array(), f)
+    }
+}
+
+impl From<[u8; 16]> for Block {
+    fn from(bytes: [u8; 16]) -> Self {
+        Self::from_array(bytes)
+    }
+}
+
+impl From<Block> for [u8; 16] {
+    fn from(block: Block) -> Self {
+        block.to_array()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl From<__m128i> for Block {
+    fn from(value: __m128i) -> Self {
+        Block(value)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl From<Block> for __m128i {
+    fn from(block: Block) -> Self {
+        block.0
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl From<uint8x16_t> for Block {
+    fn from(value: uint8x16_t) -> Self {
+        Block(value)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl From<Block> for uint8x16_t {
+    fn from(block: Block) -> Self {
+        block.0
+    }
+}
+
+/// Any 128-bit `core::simd` vector converts by a bitcast through
+/// `Simd<u8, 16>`, which stays in a register on every architecture
+#[cfg(feature = "nightly")]
+impl<T: core::simd::SimdElement, const N: usize> From<core::simd::Simd<T, N>> for Block
+where core::simd::Simd<T, N>: core::simd::ToBytes<Bytes = core::simd::Simd<u8, 16>>
+{
+    #[inline]
+    fn from(value: core::simd::Simd<T, N>) -> Self {
+        use core::simd::ToBytes;
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        return Block(value.to_ne_bytes().into());
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        return Block(value.to_ne_bytes().to_array());
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl<T: core::simd::SimdElement, const N: usize> From<Block> for core::simd::Simd<T, N>
+where core::simd::Simd<T, N>: core::simd::ToBytes<Bytes = core::simd::Simd<u8, 16>>
+{
+    #[inline]
+    fn from(block: Block) -> Self {
+        use core::simd::ToBytes;
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        return Self::from_ne_bytes(block.0.into());
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        return Self::from_ne_bytes(core::simd::Simd::from_array(block.0));
+    }
+}
+
+macro_rules! impl_bitop {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign:ident, $x86:ident, $arm:ident, $op:tt) => {
+        impl $trait for Block {
+            type Output = Block;
+
+            #[inline]
+            fn $method(self, rhs: Block) -> Block {
+                #[cfg(target_arch = "x86_64")]
+                return Block(unsafe { $x86(self.0, rhs.0) });
+                #[cfg(target_arch = "aarch64")]
+                return Block(unsafe { $arm(self.0, rhs.0) });
+                #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+                return Block(core::array::from_fn(|ii| self.0[ii] $op rhs.0[ii]));
+            }
+        }
+
+        impl $assign_trait for Block {
+            #[inline]
+            fn $assign(&mut self, rhs: Block) {
+                *self = $trait::$method(*self, rhs);
+            }
+        }
+    };
+}
+
+impl_bitop!(BitXor, bitxor, BitXorAssign, bitxor_assign, _mm_xor_si128, veorq_u8, ^);
+impl_bitop!(BitAnd, bitand, BitAndAssign, bitand_assign, _mm_and_si128, vandq_u8, &);
+impl_bitop!(BitOr, bitor, BitOrAssign, bitor_assign, _mm_or_si128, vorrq_u8, |);
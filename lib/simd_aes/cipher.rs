@@ -0,0 +1,176 @@
+//! The AES-128/192/256 block ciphers, built from the `SimdAes` rounds.
+//!
+//! Decryption uses the equivalent inverse cipher from FIPS-197, so that it
+//! runs on `aes_dec`/`aes_dec_last` with InvMixColumns applied to the middle
+//! round keys.
+
+use super::{Block, SimdAes};
+use super::gf256::{sbox, xtime};
+
+/// Expand `key` into `round_keys.len()` round keys with the FIPS-197 key
+/// schedule
+fn expand_key(key: &[u8], round_keys: &mut [Block]) {
+    let nk = key.len() / 4;
+    let mut words = [[0_u8; 4]; 60];
+    for (word, chunk) in words.iter_mut().zip(key.chunks_exact(4)) {
+        word.copy_from_slice(chunk);
+    }
+
+    let mut rcon = 1_u8;
+    for ii in nk..4 * round_keys.len() {
+        let mut temp = words[ii - 1];
+        if ii % nk == 0 {
+            temp = [
+                sbox(temp[1]) ^ rcon,
+                sbox(temp[2]),
+                sbox(temp[3]),
+                sbox(temp[0]),
+            ];
+            rcon = xtime(rcon);
+        } else if nk > 6 && ii % nk == 4 {
+            temp = temp.map(sbox);
+        }
+        words[ii] = core::array::from_fn(|jj| words[ii - nk][jj] ^ temp[jj]);
+    }
+
+    for (round_key, words) in round_keys.iter_mut().zip(words.chunks_exact(4)) {
+        *round_key = Block::from_array(core::array::from_fn(|ii| words[ii / 4][ii % 4]));
+    }
+}
+
+macro_rules! aes_cipher {
+    ($name:ident, $key_len:literal, $rounds:literal, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone)]
+        pub struct $name {
+            enc_keys: [Block; $rounds + 1],
+            dec_keys: [Block; $rounds + 1],
+        }
+
+        impl $name {
+            /// Number of rounds
+            pub const ROUNDS: usize = $rounds;
+
+            /// Run the key schedule for `key`
+            pub fn new(key: &[u8; $key_len]) -> Self {
+                let mut enc_keys = [Block::ZERO; $rounds + 1];
+                expand_key(key, &mut enc_keys);
+
+                let mut dec_keys = enc_keys;
+                dec_keys.reverse();
+                for key in &mut dec_keys[1..$rounds] {
+                    *key = key.inv_mix_columns();
+                }
+                Self { enc_keys, dec_keys }
+            }
+
+            /// The encryption round keys
+            pub fn round_keys(&self) -> &[Block; $rounds + 1] {
+                &self.enc_keys
+            }
+
+            /// The round keys of the equivalent inverse cipher, in the order
+            /// they are used
+            pub fn inv_round_keys(&self) -> &[Block; $rounds + 1] {
+                &self.dec_keys
+            }
+
+            /// Encrypt one block
+            #[inline]
+            pub fn encrypt(&self, block: Block) -> Block {
+                let mut state = block ^ self.enc_keys[0];
+                for &key in &self.enc_keys[1..$rounds] {
+                    state = state.aes_enc(key);
+                }
+                state.aes_enc_last(self.enc_keys[$rounds])
+            }
+
+            /// Decrypt one block
+            #[inline]
+            pub fn decrypt(&self, block: Block) -> Block {
+                let mut state = block ^ self.dec_keys[0];
+                for &key in &self.dec_keys[1..$rounds] {
+                    state = state.aes_dec(key);
+                }
+                state.aes_dec_last(self.dec_keys[$rounds])
+            }
+        }
+
+        /// Prints only the name, not the key
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+    };
+}
+
+aes_cipher!(Aes128, 16, 10, "AES with a 128-bit key");
+aes_cipher!(Aes192, 24, 12, "AES with a 192-bit key");
+aes_cipher!(Aes256, 32, 14, "AES with a 256-bit key");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes<const N: usize>() -> [u8; N] {
+        core::array::from_fn(|ii| ii as u8)
+    }
+
+    const PLAIN: Block = Block::from_array([
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+        0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+    ]);
+
+    /// FIPS-197 appendix A.1
+    #[test]
+    fn key_expansion() {
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6,
+            0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c,
+        ];
+        let last = [
+            0xd0, 0x14, 0xf9, 0xa8, 0xc9, 0xee, 0x25, 0x89,
+            0xe1, 0x3f, 0x0c, 0xc8, 0xb6, 0x63, 0x0c, 0xa6,
+        ];
+        let aes = Aes128::new(&key);
+        assert_eq!(aes.round_keys()[0], Block::from_array(key));
+        assert_eq!(aes.round_keys()[10], Block::from_array(last));
+    }
+
+    /// FIPS-197 appendix C.1
+    #[test]
+    fn aes128() {
+        let aes = Aes128::new(&bytes());
+        let cipher = Block::from_array([
+            0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30,
+            0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a,
+        ]);
+        assert_eq!(aes.encrypt(PLAIN), cipher);
+        assert_eq!(aes.decrypt(cipher), PLAIN);
+    }
+
+    /// FIPS-197 appendix C.2
+    #[test]
+    fn aes192() {
+        let aes = Aes192::new(&bytes());
+        let cipher = Block::from_array([
+            0xdd, 0xa9, 0x7c, 0xa4, 0x86, 0x4c, 0xdf, 0xe0,
+            0x6e, 0xaf, 0x70, 0xa0, 0xec, 0x0d, 0x71, 0x91,
+        ]);
+        assert_eq!(aes.encrypt(PLAIN), cipher);
+        assert_eq!(aes.decrypt(cipher), PLAIN);
+    }
+
+    /// FIPS-197 appendix C.3
+    #[test]
+    fn aes256() {
+        let aes = Aes256::new(&bytes());
+        let cipher = Block::from_array([
+            0x8e, 0xa2, 0xb7, 0xca, 0x51, 0x67, 0x45, 0xbf,
+            0xea, 0xfc, 0x49, 0x90, 0x4b, 0x49, 0x60, 0x89,
+        ]);
+        assert_eq!(aes.encrypt(PLAIN), cipher);
+        assert_eq!(aes.decrypt(cipher), PLAIN);
+    }
+}
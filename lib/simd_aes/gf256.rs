@@ -0,0 +1,264 @@
+//! Arithmetic in GF(2^8) modulo the AES polynomial `x^8 + x^4 + x^3 + x + 1`.
+//!
+//! The scalar functions are branch-free and `const`; the lane functions
+//! apply them to every byte of a `Block`, which the compiler vectorizes.
+//! `LOG`/`EXP` are tables to the generator `0x03` and `SBOX`/`INV_SBOX` are
+//! the AES S-boxes, computed at compile time from the field operations.
+
+use super::Block;
+
+/// The AES reduction polynomial without the `x^8` term
+pub const POLY: u8 = 0x1b;
+
+/// The generator used for `LOG` and `EXP`
+pub const GENERATOR: u8 = 0x03;
+
+/// Multiplication by `x`
+#[inline]
+pub const fn xtime(a: u8) -> u8 {
+    (a << 1) ^ (POLY & 0_u8.wrapping_sub(a >> 7))
+}
+
+/// Multiplication
+#[inline]
+pub const fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    let mut bit = 0;
+    while bit < 8 {
+        product ^= a & 0_u8.wrapping_sub(b & 1);
+        a = xtime(a);
+        b >>= 1;
+        bit += 1;
+    }
+    product
+}
+
+/// `a` to the power `n`, with `0^0 = 1`
+#[inline]
+pub const fn pow(a: u8, mut n: u32) -> u8 {
+    let mut square = a;
+    let mut result = 1;
+    while n != 0 {
+        if n & 1 != 0 {
+            result = mul(result, square);
+        }
+        square = mul(square, square);
+        n >>= 1;
+    }
+    result
+}
+
+/// Multiplicative inverse as `a^254`, with `0` mapping to `0`
+#[inline]
+pub const fn inv(a: u8) -> u8 {
+    let a2 = mul(a, a);
+    let a3 = mul(a2, a);
+    let a6 = mul(a3, a3);
+    let a12 = mul(a6, a6);
+    let a15 = mul(a12, a3);
+    let a30 = mul(a15, a15);
+    let a60 = mul(a30, a30);
+    let a120 = mul(a60, a60);
+    let a240 = mul(a120, a120);
+    let a252 = mul(a240, a12);
+    mul(a252, a2)
+}
+
+/// `EXP[i] = GENERATOR^i`; `EXP[255]` wraps around to `1`
+pub const EXP: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut value = 1;
+    let mut ii = 0;
+    while ii < 256 {
+        table[ii] = value;
+        value = mul(value, GENERATOR);
+        ii += 1;
+    }
+    table
+};
+
+/// `LOG[a]` is the `i < 255` with `GENERATOR^i = a`; `LOG[0]` is unused and 0
+pub const LOG: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut ii = 0;
+    while ii < 255 {
+        table[EXP[ii] as usize] = ii as u8;
+        ii += 1;
+    }
+    table
+};
+
+/// Discrete logarithm to `GENERATOR`, `None` for `0`
+#[inline]
+pub const fn log(a: u8) -> Option<u8> {
+    if a == 0 {
+        return None;
+    }
+    Some(LOG[a as usize])
+}
+
+/// `GENERATOR^n`
+#[inline]
+pub const fn exp(n: u8) -> u8 {
+    EXP[n as usize]
+}
+
+/// The AES S-box: the inverse followed by the affine map
+#[inline]
+pub const fn sbox(a: u8) -> u8 {
+    let b = inv(a);
+    b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63
+}
+
+/// The inverse AES S-box
+#[inline]
+pub const fn inv_sbox(a: u8) -> u8 {
+    inv(a.rotate_left(1) ^ a.rotate_left(3) ^ a.rotate_left(6) ^ 0x05)
+}
+
+/// The AES S-box as a table
+pub const SBOX: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut ii = 0;
+    while ii < 256 {
+        table[ii] = sbox(ii as u8);
+        ii += 1;
+    }
+    table
+};
+
+/// The inverse AES S-box as a table
+pub const INV_SBOX: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut ii = 0;
+    while ii < 256 {
+        table[ii] = inv_sbox(ii as u8);
+        ii += 1;
+    }
+    table
+};
+
+/// `xtime` on every byte
+#[inline]
+pub fn xtime_lanes(a: Block) -> Block {
+    Block::from_array(a.to_array().map(xtime))
+}
+
+/// Multiplication of every byte of `a` by the same byte of `b`
+#[inline]
+pub fn mul_lanes(a: Block, b: Block) -> Block {
+    let (a, b) = (a.to_array(), b.to_array());
+    Block::from_array(core::array::from_fn(|ii| mul(a[ii], b[ii])))
+}
+
+/// Multiplication of every byte by `b`
+#[inline]
+pub fn mul_lanes_by(a: Block, b: u8) -> Block {
+    Block::from_array(a.to_array().map(|a| mul(a, b)))
+}
+
+/// `inv` on every byte
+#[inline]
+pub fn inv_lanes(a: Block) -> Block {
+    Block::from_array(a.to_array().map(inv))
+}
+
+/// The lane functions for `core::simd` vectors of any width
+#[cfg(feature = "nightly")]
+mod nightly {
+    use core::simd::Simd;
+    use super::POLY;
+
+    /// `xtime` on every lane
+    #[inline]
+    pub fn xtime_simd<const N: usize>(a: Simd<u8, N>) -> Simd<u8, N> {
+        let carry = Simd::splat(0) - (a >> 7);
+        (a << 1) ^ (carry & Simd::splat(POLY))
+    }
+
+    /// Multiplication of every lane of `a` by the same lane of `b`
+    #[inline]
+    pub fn mul_simd<const N: usize>(mut a: Simd<u8, N>, mut b: Simd<u8, N>) -> Simd<u8, N> {
+        let mut product = Simd::splat(0);
+        for _ in 0..8 {
+            product ^= a & (Simd::splat(0) - (b & Simd::splat(1)));
+            a = xtime_simd(a);
+            b >>= 1;
+        }
+        product
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub use nightly::{mul_simd, xtime_simd};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_matches_log_tables() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                let expected = match (log(a), log(b)) {
+                    (Some(la), Some(lb)) => exp(((la as u32 + lb as u32) % 255) as u8),
+                    _ => 0,
+                };
+                assert_eq!(mul(a, b), expected);
+            }
+        }
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+    }
+
+    #[test]
+    fn inverse_and_logs() {
+        assert_eq!(inv(0), 0);
+        for a in 1..=255 {
+            assert_eq!(mul(a, inv(a)), 1);
+            assert_eq!(exp(log(a).unwrap()), a);
+            assert_eq!(pow(a, 255), 1);
+        }
+        // The generator has order 255
+        let mut seen = [false; 256];
+        for &value in &EXP[..255] {
+            assert!(!seen[value as usize]);
+            seen[value as usize] = true;
+        }
+    }
+
+    #[test]
+    fn sbox_tables() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x01], 0x7c);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(SBOX[0xff], 0x16);
+        for a in 0..=255 {
+            assert_eq!(INV_SBOX[SBOX[a as usize] as usize], a);
+            assert_eq!(inv_sbox(sbox(a)), a);
+        }
+    }
+
+    #[test]
+    fn lanes_match_scalar() {
+        let a = Block::from_array(core::array::from_fn(|ii| (ii * 37 + 5) as u8));
+        let b = Block::from_array(core::array::from_fn(|ii| (ii * 91 + 200) as u8));
+        let (aa, bb) = (a.to_array(), b.to_array());
+        let product = mul_lanes(a, b).to_array();
+        let scaled = mul_lanes_by(a, 0x1d).to_array();
+        let doubled = xtime_lanes(a).to_array();
+        let inverse = inv_lanes(a).to_array();
+        for ii in 0..16 {
+            assert_eq!(product[ii], mul(aa[ii], bb[ii]));
+            assert_eq!(scaled[ii], mul(aa[ii], 0x1d));
+            assert_eq!(doubled[ii], xtime(aa[ii]));
+            assert_eq!(inverse[ii], inv(aa[ii]));
+        }
+        #[cfg(feature = "nightly")]
+        {
+            use core::simd::Simd;
+            let product = mul_simd(Simd::from_array(aa), Simd::from_array(bb));
+            assert_eq!(product.to_array(), mul_lanes(a, b).to_array());
+        }
+    }
+}
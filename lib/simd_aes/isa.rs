@@ -0,0 +1,424 @@
+//! Backends known at compile time, for kernels run through `dispatch`.
+//!
+//! Every backend has a zero-sized marker implementing `Isa`, whose rounds
+//! call that backend directly. A kernel generic over the marker is compiled
+//! once per backend inside the `#[target_feature]` function `dispatch`
+//! picks; the rounds are `#[inline(always)]` so they, and a kernel that is
+//! `#[inline(always)]` itself, flatten into that function with the
+//! intrinsics inlined and no check of the detected backend per operation.
+//!
+//! Only `dispatch` hands out the hardware markers, so holding one proves
+//! the CPU supports its instructions.
+
+use core::fmt;
+use super::{Block, Block4, SimdAes, soft};
+use super::backend::{Backend, backend};
+
+/// The rounds of one backend, on single blocks and on `Block4`.
+///
+/// The `Block4` rounds run lane by lane unless the backend has wider
+/// instructions.
+pub trait Isa: Copy + fmt::Debug + Send + Sync + 'static {
+    /// The backend the rounds run on
+    fn backend(self) -> Backend;
+
+    /// Multiply every column by the MixColumns polynomial
+    fn mix_columns(self, block: Block) -> Block;
+    /// Multiply every column by the InvMixColumns polynomial
+    fn inv_mix_columns(self, block: Block) -> Block;
+    /// `SimdAes::aes_enc`
+    fn aes_enc(self, block: Block, key: Block) -> Block;
+    /// `SimdAes::aes_enc_last`
+    fn aes_enc_last(self, block: Block, key: Block) -> Block;
+    /// `SimdAes::aes_dec`
+    fn aes_dec(self, block: Block, key: Block) -> Block;
+    /// `SimdAes::aes_dec_last`
+    fn aes_dec_last(self, block: Block, key: Block) -> Block;
+
+    /// `mix_columns` on four blocks
+    #[inline(always)]
+    fn mix_columns4(self, blocks: Block4) -> Block4 {
+        Block4(blocks.0.map(|block| self.mix_columns(block)))
+    }
+
+    /// `inv_mix_columns` on four blocks
+    #[inline(always)]
+    fn inv_mix_columns4(self, blocks: Block4) -> Block4 {
+        Block4(blocks.0.map(|block| self.inv_mix_columns(block)))
+    }
+
+    /// `aes_enc` on four blocks
+    #[inline(always)]
+    fn aes_enc4(self, blocks: Block4, keys: Block4) -> Block4 {
+        Block4(core::array::from_fn(|ii| self.aes_enc(blocks.0[ii], keys.0[ii])))
+    }
+
+    /// `aes_enc_last` on four blocks
+    #[inline(always)]
+    fn aes_enc_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        Block4(core::array::from_fn(|ii| self.aes_enc_last(blocks.0[ii], keys.0[ii])))
+    }
+
+    /// `aes_dec` on four blocks
+    #[inline(always)]
+    fn aes_dec4(self, blocks: Block4, keys: Block4) -> Block4 {
+        Block4(core::array::from_fn(|ii| self.aes_dec(blocks.0[ii], keys.0[ii])))
+    }
+
+    /// `aes_dec_last` on four blocks
+    #[inline(always)]
+    fn aes_dec_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        Block4(core::array::from_fn(|ii| self.aes_dec_last(blocks.0[ii], keys.0[ii])))
+    }
+
+    /// `SimdAes::inv_aes_dec_rounds`
+    #[inline(always)]
+    fn inv_aes_dec_rounds(self, block: Block, key: Block, rounds: usize) -> Block {
+        if rounds == 0 {
+            return block;
+        }
+        let zero = Block::ZERO;
+        let mixed_key = if key == zero { zero } else { self.mix_columns(key) };
+        let mut state = self.mix_columns(block ^ key);
+        for _ in 1..rounds {
+            state = self.aes_enc(state, mixed_key);
+        }
+        self.aes_enc_last(state, zero)
+    }
+
+    /// `SimdAes::inv_aes_dec_rounds` on four blocks
+    #[inline(always)]
+    fn inv_aes_dec_rounds4(self, blocks: Block4, key: Block4, rounds: usize) -> Block4 {
+        if rounds == 0 {
+            return blocks;
+        }
+        let zero = Block4::ZERO;
+        let mixed_key = if key == zero { zero } else { self.mix_columns4(key) };
+        let mut state = self.mix_columns4(blocks ^ key);
+        for _ in 1..rounds {
+            state = self.aes_enc4(state, mixed_key);
+        }
+        self.aes_enc_last4(state, zero)
+    }
+}
+
+/// Portable software rounds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Soft;
+
+impl Isa for Soft {
+    #[inline(always)]
+    fn backend(self) -> Backend {
+        Backend::Soft
+    }
+
+    #[inline(always)]
+    fn mix_columns(self, block: Block) -> Block {
+        soft::mix_columns(block)
+    }
+
+    #[inline(always)]
+    fn inv_mix_columns(self, block: Block) -> Block {
+        soft::inv_mix_columns(block)
+    }
+
+    #[inline(always)]
+    fn aes_enc(self, block: Block, key: Block) -> Block {
+        soft::aes_enc(block, key)
+    }
+
+    #[inline(always)]
+    fn aes_enc_last(self, block: Block, key: Block) -> Block {
+        soft::aes_enc_last(block, key)
+    }
+
+    #[inline(always)]
+    fn aes_dec(self, block: Block, key: Block) -> Block {
+        soft::aes_dec(block, key)
+    }
+
+    #[inline(always)]
+    fn aes_dec_last(self, block: Block, key: Block) -> Block {
+        soft::aes_dec_last(block, key)
+    }
+}
+
+/// The `SimdAes` rounds of `Block` and `Block4`, checking the detected
+/// backend on every operation. For code not run through `dispatch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Detected;
+
+impl Isa for Detected {
+    #[inline(always)]
+    fn backend(self) -> Backend {
+        backend()
+    }
+
+    #[inline(always)]
+    fn mix_columns(self, block: Block) -> Block {
+        block.mix_columns()
+    }
+
+    #[inline(always)]
+    fn inv_mix_columns(self, block: Block) -> Block {
+        block.inv_mix_columns()
+    }
+
+    #[inline(always)]
+    fn aes_enc(self, block: Block, key: Block) -> Block {
+        block.aes_enc(key)
+    }
+
+    #[inline(always)]
+    fn aes_enc_last(self, block: Block, key: Block) -> Block {
+        block.aes_enc_last(key)
+    }
+
+    #[inline(always)]
+    fn aes_dec(self, block: Block, key: Block) -> Block {
+        block.aes_dec(key)
+    }
+
+    #[inline(always)]
+    fn aes_dec_last(self, block: Block, key: Block) -> Block {
+        block.aes_dec_last(key)
+    }
+
+    #[inline(always)]
+    fn aes_enc4(self, blocks: Block4, keys: Block4) -> Block4 {
+        blocks.aes_enc(keys)
+    }
+
+    #[inline(always)]
+    fn aes_enc_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        blocks.aes_enc_last(keys)
+    }
+
+    #[inline(always)]
+    fn aes_dec4(self, blocks: Block4, keys: Block4) -> Block4 {
+        blocks.aes_dec(keys)
+    }
+
+    #[inline(always)]
+    fn aes_dec_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        blocks.aes_dec_last(keys)
+    }
+}
+
+/// Hardware markers: the field keeps them from being built outside this
+/// crate, `dispatch` creates them after detecting the backend.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+macro_rules! hardware_marker {
+    ($name:ident, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub struct $name(());
+
+        impl $name {
+            /// # Safety
+            /// The CPU must support the backend's instructions.
+            #[inline(always)]
+            pub(crate) unsafe fn new_unchecked() -> Self {
+                $name(())
+            }
+        }
+    };
+}
+
+/// Single-block rounds through an intrinsics module with the functions of
+/// `aesni` or `arm_crypto`
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+macro_rules! block_rounds {
+    ($module:ident, $backend:ident) => {
+        #[inline(always)]
+        fn backend(self) -> Backend {
+            Backend::$backend
+        }
+
+        #[inline(always)]
+        fn mix_columns(self, block: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::mix_columns(block) }
+        }
+
+        #[inline(always)]
+        fn inv_mix_columns(self, block: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::inv_mix_columns(block) }
+        }
+
+        #[inline(always)]
+        fn aes_enc(self, block: Block, key: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::aes_enc(block, key) }
+        }
+
+        #[inline(always)]
+        fn aes_enc_last(self, block: Block, key: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::aes_enc_last(block, key) }
+        }
+
+        #[inline(always)]
+        fn aes_dec(self, block: Block, key: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::aes_dec(block, key) }
+        }
+
+        #[inline(always)]
+        fn aes_dec_last(self, block: Block, key: Block) -> Block {
+            // SAFETY: the marker proves the instructions are available.
+            unsafe { crate::$module::aes_dec_last(block, key) }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+hardware_marker!(AesNi, "x86_64 AES-NI, one block per instruction");
+#[cfg(target_arch = "x86_64")]
+hardware_marker!(Vaes256, "x86_64 VAES with AVX2, two blocks per instruction");
+#[cfg(target_arch = "x86_64")]
+hardware_marker!(Vaes512, "x86_64 VAES with AVX-512, four blocks per instruction");
+#[cfg(target_arch = "aarch64")]
+hardware_marker!(ArmCrypto, "aarch64 cryptographic extension");
+
+#[cfg(target_arch = "x86_64")]
+impl Isa for AesNi {
+    block_rounds!(aesni, AesNi);
+}
+
+/// `Block4` rounds as two 256-bit halves
+#[cfg(target_arch = "x86_64")]
+macro_rules! vaes256_rounds {
+    ($($name:ident => $intrinsic:ident),*) => {
+        $(
+            #[inline(always)]
+            fn $name(self, blocks: Block4, keys: Block4) -> Block4 {
+                let ([a, b], [c, d]) = (blocks.halves(), keys.halves());
+                // SAFETY: the marker proves the instructions are available.
+                unsafe { Block4::from_halves([vaes::$intrinsic(a, c), vaes::$intrinsic(b, d)]) }
+            }
+        )*
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+use super::wide::vaes;
+
+#[cfg(target_arch = "x86_64")]
+impl Isa for Vaes256 {
+    block_rounds!(aesni, Vaes256);
+
+    #[inline(always)]
+    fn mix_columns4(self, blocks: Block4) -> Block4 {
+        self.aes_enc4(self.aes_dec_last4(blocks, Block4::ZERO), Block4::ZERO)
+    }
+
+    vaes256_rounds!(
+        aes_enc4 => aes_enc2,
+        aes_enc_last4 => aes_enc_last2,
+        aes_dec4 => aes_dec2,
+        aes_dec_last4 => aes_dec_last2
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Isa for Vaes512 {
+    block_rounds!(aesni, Vaes512);
+
+    #[inline(always)]
+    fn mix_columns4(self, blocks: Block4) -> Block4 {
+        self.aes_enc4(self.aes_dec_last4(blocks, Block4::ZERO), Block4::ZERO)
+    }
+
+    #[inline(always)]
+    fn aes_enc4(self, blocks: Block4, keys: Block4) -> Block4 {
+        // SAFETY: the marker proves the instructions are available.
+        unsafe { vaes::aes_enc4(blocks, keys) }
+    }
+
+    #[inline(always)]
+    fn aes_enc_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        // SAFETY: the marker proves the instructions are available.
+        unsafe { vaes::aes_enc_last4(blocks, keys) }
+    }
+
+    #[inline(always)]
+    fn aes_dec4(self, blocks: Block4, keys: Block4) -> Block4 {
+        // SAFETY: the marker proves the instructions are available.
+        unsafe { vaes::aes_dec4(blocks, keys) }
+    }
+
+    #[inline(always)]
+    fn aes_dec_last4(self, blocks: Block4, keys: Block4) -> Block4 {
+        // SAFETY: the marker proves the instructions are available.
+        unsafe { vaes::aes_dec_last4(blocks, keys) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Isa for ArmCrypto {
+    block_rounds!(arm_crypto, ArmCrypto);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch;
+    use crate::backend::Kernel;
+    use crate::test_util::blocks4;
+
+    /// Every round through the marker, to compare with `SimdAes`
+    struct Rounds(Block4, Block4);
+
+    impl Kernel for Rounds {
+        type Output = (Backend, [Block4; 11], Block);
+
+        #[inline(always)]
+        fn run<I: Isa>(self, isa: I) -> Self::Output {
+            let Rounds(blocks, keys) = self;
+            let rounds = [
+                isa.mix_columns4(blocks),
+                isa.inv_mix_columns4(blocks),
+                isa.aes_enc4(blocks, keys),
+                isa.aes_enc_last4(blocks, keys),
+                isa.aes_dec4(blocks, keys),
+                isa.aes_dec_last4(blocks, keys),
+                isa.inv_aes_dec_rounds4(blocks, keys, 4),
+                isa.inv_aes_dec_rounds4(blocks, Block4::ZERO, 4),
+                Block4(blocks.0.map(|block| isa.aes_enc(block, keys.0[0]))),
+                Block4(blocks.0.map(|block| isa.aes_dec_last(block, keys.0[1]))),
+                Block4(blocks.0.map(|block| isa.inv_aes_dec_rounds(block, keys.0[2], 3))),
+            ];
+            (isa.backend(), rounds, isa.inv_mix_columns(blocks.0[3]))
+        }
+    }
+
+    fn expected(blocks: Block4, keys: Block4) -> ([Block4; 11], Block) {
+        let rounds = [
+            blocks.mix_columns(),
+            blocks.inv_mix_columns(),
+            blocks.aes_enc(keys),
+            blocks.aes_enc_last(keys),
+            blocks.aes_dec(keys),
+            blocks.aes_dec_last(keys),
+            blocks.inv_aes_dec_rounds(keys, 4),
+            blocks.inv_aes_dec_rounds(Block4::ZERO, 4),
+            Block4(blocks.0.map(|block| block.aes_enc(keys.0[0]))),
+            Block4(blocks.0.map(|block| block.aes_dec_last(keys.0[1]))),
+            Block4(blocks.0.map(|block| block.inv_aes_dec_rounds(keys.0[2], 3))),
+        ];
+        (rounds, blocks.0[3].inv_mix_columns())
+    }
+
+    #[test]
+    fn markers_match_simd_aes() {
+        for (blocks, keys) in blocks4(100).zip(blocks4(101).skip(1)) {
+            let (backend, rounds, single) = dispatch(Rounds(blocks, keys));
+            assert_eq!(backend, crate::backend());
+            assert_eq!((rounds, single), expected(blocks, keys));
+            assert_eq!(Rounds(blocks, keys).run(Soft), (Backend::Soft, rounds, single));
+            assert_eq!(Rounds(blocks, keys).run(Detected), (backend, rounds, single));
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Known-answer tests from the FIPS-197 appendix C.1 AES-128 example, run
+//! against every backend, for the rounds, their inverses and the steps they
+//! are made of.
+
+use super::{Bitsliced, Block, Block2, Block4, SimdAes};
+use super::soft::Soft;
+
+const fn hex(s: &str) -> Block {
+    const fn digit(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            _ => c - b'a' + 10,
+        }
+    }
+    let s = s.as_bytes();
+    let mut bytes = [0; 16];
+    let mut ii = 0;
+    while ii < 16 {
+        bytes[ii] = digit(s[2 * ii]) << 4 | digit(s[2 * ii + 1]);
+        ii += 1;
+    }
+    Block::from_array(bytes)
+}
+
+const INPUT: Block = hex("00112233445566778899aabbccddeeff");
+const OUTPUT: Block = hex("69c4e0d86a7b0430d8cdb78070b4c55a");
+
+/// `round[r].start` for `r` in 1..=10
+const START: [Block; 10] = [
+    hex("00102030405060708090a0b0c0d0e0f0"),
+    hex("89d810e8855ace682d1843d8cb128fe4"),
+    hex("4915598f55e5d7a0daca94fa1f0a63f7"),
+    hex("fa636a2825b339c940668a3157244d17"),
+    hex("247240236966b3fa6ed2753288425b6c"),
+    hex("c81677bc9b7ac93b25027992b0261996"),
+    hex("c62fe109f75eedc3cc79395d84f9cf5d"),
+    hex("d1876c0f79c4300ab45594add66ff41f"),
+    hex("fde3bad205e5d0d73547964ef1fe37f1"),
+    hex("bd6e7c3df2b5779e0b61216e8b10b689"),
+];
+
+/// `round[r].s_row` for `r` in 1..=10
+const S_ROW: [Block; 10] = [
+    hex("6353e08c0960e104cd70b751bacad0e7"),
+    hex("a7be1a6997ad739bd8c9ca451f618b61"),
+    hex("3bd92268fc74fb735767cbe0c0590e2d"),
+    hex("2d6d7ef03f33e334093602dd5bfb12c7"),
+    hex("36339d50f9b539269f2c092dc4406d23"),
+    hex("e8dab6901477d4653ff7f5e2e747dd4f"),
+    hex("b458124c68b68a014b99f82e5f15554c"),
+    hex("3e1c22c0b6fcbf768da85067f6170495"),
+    hex("54d990a16ba09ab596bbf40ea111702f"),
+    hex("7ad5fda789ef4e272bca100b3d9ff59f"),
+];
+
+/// `round[1].s_box`
+const S_BOX: Block = hex("63cab7040953d051cd60e0e7ba70e18c");
+/// `round[1].m_col`
+const M_COL: Block = hex("5f72641557f5bc92f7be3b291db9f91a");
+
+/// `round[r].k_sch` for `r` in 0..=10
+const KEY: [Block; 11] = [
+    hex("000102030405060708090a0b0c0d0e0f"),
+    hex("d6aa74fdd2af72fadaa678f1d6ab76fe"),
+    hex("b692cf0b643dbdf1be9bc5006830b3fe"),
+    hex("b6ff744ed2c2c9bf6c590cbf0469bf41"),
+    hex("47f7f7bc95353e03f96c32bcfd058dfd"),
+    hex("3caaa3e8a99f9deb50f3af57adf622aa"),
+    hex("5e390f7df7a69296a7553dc10aa31f6b"),
+    hex("14f9701ae35fe28c440adf4d4ea9c026"),
+    hex("47438735a41c65b9e016baf4aebf7ad2"),
+    hex("549932d1f08557681093ed9cbe2c974e"),
+    hex("13111d7fe3944a17f307a78b4d2b30c5"),
+];
+
+/// InvMixColumns of `KEY[r]` for `r` in 1..=9, the equivalent inverse
+/// cipher round keys
+const INV_KEY: [Block; 9] = [
+    hex("8c56dff0825dd3f9805ad3fc8659d7fd"),
+    hex("a0db02992286d160a2dc029c2485d561"),
+    hex("c7c6e391e54032f1479c306d6319e50c"),
+    hex("a8a2f5044de2c7f50a7ef79869671294"),
+    hex("2ec410276326d7d26958204a003f32de"),
+    hex("72e3098d11c5de5f789dfe1578a2cccb"),
+    hex("8d82fc749c47222be4dadc3e9c7810f5"),
+    hex("1362a4638f2586486bff5a76f7874a83"),
+    hex("13aa29be9c8faff6f770f58000f7bf03"),
+];
+
+/// Run the known-answer tests on the backend `T`
+fn check<T: SimdAes>(wrap: fn(Block) -> T, unwrap: fn(T) -> Block) {
+    let enc = |state: Block, key: Block| unwrap(wrap(state).aes_enc(wrap(key)));
+    let enc_last = |state: Block, key: Block| unwrap(wrap(state).aes_enc_last(wrap(key)));
+    let dec = |state: Block, key: Block| unwrap(wrap(state).aes_dec(wrap(key)));
+    let dec_last = |state: Block, key: Block| unwrap(wrap(state).aes_dec_last(wrap(key)));
+
+    assert_eq!(INPUT ^ KEY[0], START[0]);
+    for round in 0..9 {
+        assert_eq!(enc(START[round], KEY[round + 1]), START[round + 1], "aes_enc round {}", round + 1);
+    }
+    assert_eq!(enc_last(START[9], KEY[10]), OUTPUT, "aes_enc_last");
+    for round in 0..10 {
+        assert_eq!(enc_last(START[round], Block::ZERO), S_ROW[round], "s_row round {}", round + 1);
+        assert_eq!(dec_last(S_ROW[round], Block::ZERO), START[round], "aes_dec_last round {}", round + 1);
+    }
+
+    // Equivalent inverse cipher
+    let mut state = OUTPUT ^ KEY[10];
+    for round in (1..10).rev() {
+        state = dec(state, INV_KEY[round - 1]);
+    }
+    assert_eq!(dec_last(state, KEY[0]), INPUT, "equivalent inverse cipher");
+    for round in 0..9 {
+        assert_eq!(dec(S_ROW[round + 1], INV_KEY[round]), S_ROW[round], "aes_dec round {}", round + 1);
+    }
+
+    let inv_enc = |state: Block, key: Block| unwrap(wrap(state).inv_aes_enc(wrap(key)));
+    let inv_enc_last = |state: Block, key: Block| unwrap(wrap(state).inv_aes_enc_last(wrap(key)));
+    let inv_dec = |state: Block, key: Block| unwrap(wrap(state).inv_aes_dec(wrap(key)));
+    let inv_dec_last = |state: Block, key: Block| unwrap(wrap(state).inv_aes_dec_last(wrap(key)));
+
+    for round in 0..9 {
+        assert_eq!(inv_enc(START[round + 1], KEY[round + 1]), START[round], "inv_aes_enc round {}", round + 1);
+        assert_eq!(inv_dec(S_ROW[round], INV_KEY[round]), S_ROW[round + 1], "inv_aes_dec round {}", round + 1);
+    }
+    assert_eq!(inv_enc_last(OUTPUT, KEY[10]), START[9], "inv_aes_enc_last");
+    for round in 0..10 {
+        assert_eq!(inv_dec_last(START[round], Block::ZERO), S_ROW[round], "inv_aes_dec_last round {}", round + 1);
+    }
+
+    for (rounds, &key) in KEY.iter().enumerate() {
+        let mut enc_state = wrap(INPUT);
+        let mut dec_state = wrap(INPUT);
+        for _ in 0..rounds {
+            enc_state = enc_state.aes_enc(wrap(key));
+            dec_state = dec_state.aes_dec(wrap(key));
+        }
+        let enc_state = enc_state.inv_aes_enc_rounds(wrap(key), rounds);
+        let dec_state = dec_state.inv_aes_dec_rounds(wrap(key), rounds);
+        assert_eq!(unwrap(enc_state), INPUT, "inv_aes_enc_rounds {rounds}");
+        assert_eq!(unwrap(dec_state), INPUT, "inv_aes_dec_rounds {rounds}");
+    }
+
+    let step = |state: Block, f: fn(T) -> T| unwrap(f(wrap(state)));
+    assert_eq!(step(START[0], T::sub_bytes), S_BOX, "sub_bytes");
+    assert_eq!(step(S_BOX, T::shift_rows), S_ROW[0], "shift_rows");
+    assert_eq!(step(S_ROW[0], T::mix_columns), M_COL, "mix_columns");
+    assert_eq!(step(M_COL, T::inv_mix_columns), S_ROW[0], "inv_mix_columns");
+    assert_eq!(step(S_ROW[0], T::inv_shift_rows), S_BOX, "inv_shift_rows");
+    assert_eq!(step(S_BOX, T::inv_sub_bytes), START[0], "inv_sub_bytes");
+
+    for (&state, &key) in START.iter().zip(&KEY) {
+        let state = wrap(state);
+        let key = wrap(key);
+        let enc = state.sub_bytes().shift_rows().mix_columns() ^ key;
+        let enc_last = state.sub_bytes().shift_rows() ^ key;
+        let dec = state.inv_shift_rows().inv_sub_bytes().inv_mix_columns() ^ key;
+        let dec_last = state.inv_shift_rows().inv_sub_bytes() ^ key;
+        assert!(enc == state.aes_enc(key), "aes_enc from steps");
+        assert!(enc_last == state.aes_enc_last(key), "aes_enc_last from steps");
+        assert!(dec == state.aes_dec(key), "aes_dec from steps");
+        assert!(dec_last == state.aes_dec_last(key), "aes_dec_last from steps");
+        assert!(state.sub_bytes().inv_sub_bytes() == state, "inv_sub_bytes");
+        assert!(state.shift_rows().inv_shift_rows() == state, "inv_shift_rows");
+        assert!(state.mix_columns().inv_mix_columns() == state, "inv_mix_columns");
+    }
+}
+
+#[test]
+fn block() {
+    check(|block| block, |block| block);
+}
+
+#[test]
+fn soft() {
+    check(Soft, |soft| soft.0);
+}
+
+#[test]
+fn bitsliced() {
+    check(Bitsliced::<u32>::splat, |sliced| sliced.block(17));
+    check(Bitsliced::<u64>::splat, |sliced| sliced.block(63));
+}
+
+/// The wide blocks, with the same block in every lane
+#[test]
+fn wide() {
+    fn lane<const N: usize>(blocks: [Block; N]) -> Block {
+        assert!(blocks.iter().all(|&block| block == blocks[0]), "lanes differ");
+        blocks[0]
+    }
+    check(Block2::splat, |wide| lane(wide.0));
+    check(Block4::splat, |wide| lane(wide.0));
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn simd() {
+    use core::simd::{Simd, ToBytes};
+    check(Simd::<u8, 16>::from, Block::from);
+    check(Simd::<u16, 8>::from, Block::from);
+    check(Simd::<u32, 4>::from, Block::from);
+    check(Simd::<u64, 2>::from, Block::from);
+    check(Simd::<i8, 16>::from, Block::from);
+    check(Simd::<i16, 8>::from, Block::from);
+    check(Simd::<i32, 4>::from, Block::from);
+    check(Simd::<i64, 2>::from, Block::from);
+    check(|block| Simd::<u8, 32>::from(Block2::splat(block)), |wide| Block2::from(wide).0[1]);
+    check(|block| Simd::<u8, 64>::from(Block4::splat(block)), |wide| Block4::from(wide).0[3]);
+    check(|block| Simd::<u64, 4>::from_ne_bytes(Block2::splat(block).into()), |wide| Block2::from(wide.to_ne_bytes()).0[1]);
+    check(|block| Simd::<u32, 16>::from_ne_bytes(Block4::splat(block).into()), |wide| Block4::from(wide.to_ne_bytes()).0[2]);
+}
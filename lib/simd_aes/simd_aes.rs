@@ -1,153 +1,544 @@
-#![feature(portable_simd)]
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
 
-#[cfg(target_arch = "x86_64")]
-use core::arch::x86_64::{
-    __m128i,
-    _mm_aesenc_si128,
-    _mm_aesenclast_si128,
-    _mm_aesdec_si128,
-    _mm_aesdeclast_si128,
-};
-
-use core::simd::{Simd};
+mod block;
+pub use block::Block;
+pub mod gf256;
+pub mod soft;
+mod cipher;
+mod wide;
+pub use wide::{Block2, Block4};
+mod bitslice;
+pub use bitslice::{BitWord, Bitsliced};
+pub use cipher::{Aes128, Aes192, Aes256};
+mod backend;
+pub use backend::{Backend, Kernel, backend, dispatch};
+pub mod isa;
+pub use isa::Isa;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use backend::has_hardware_aes;
+#[cfg(test)]
+mod kat;
+#[cfg(test)]
+mod test_util;
 
-#[cfg(target_arch="x86_64")]
-use core::simd::{LaneCount, SimdElement, SupportedLaneCount};
+use core::ops::BitXor;
 
-pub trait SimdAes {
-    #[cfg(target_arch = "aarch64")]
+/// AES rounds with the x86 semantics on every target: the round key is
+/// xored in after the round.
+///
+/// The state is column-major: byte `i` is row `i % 4` of column `i / 4`.
+pub trait SimdAes: Copy + Default + PartialEq + BitXor<Output = Self> {
+    /// The S-box applied to every byte
+    fn sub_bytes(self) -> Self;
+    /// The inverse S-box applied to every byte
+    fn inv_sub_bytes(self) -> Self;
+    /// Rotate row `r` left by `r` bytes
     fn shift_rows(self) -> Self;
-    #[cfg(target_arch = "aarch64")]
+    /// Rotate row `r` right by `r` bytes
     fn inv_shift_rows(self) -> Self;
+    /// Multiply every column by the MixColumns polynomial
+    fn mix_columns(self) -> Self;
+    /// Multiply every column by the InvMixColumns polynomial
+    fn inv_mix_columns(self) -> Self;
+    /// SubBytes, ShiftRows, MixColumns, then xor `key`, as `aesenc`
     fn aes_enc(self, key: Self) -> Self;
+    /// SubBytes, ShiftRows, then xor `key`, as `aesenclast`
     fn aes_enc_last(self, key: Self) -> Self;
+    /// InvShiftRows, InvSubBytes, InvMixColumns, then xor `key`, as `aesdec`
     fn aes_dec(self, key: Self) -> Self;
+    /// InvShiftRows, InvSubBytes, then xor `key`, as `aesdeclast`
     fn aes_dec_last(self, key: Self) -> Self;
+
+    /// Inverse of `aes_enc` with the same key
+    #[inline]
+    fn inv_aes_enc(self, key: Self) -> Self {
+        (self ^ key).inv_mix_columns().aes_dec_last(Self::default())
+    }
+
+    /// Inverse of `aes_enc_last` with the same key
+    #[inline]
+    fn inv_aes_enc_last(self, key: Self) -> Self {
+        (self ^ key).aes_dec_last(Self::default())
+    }
+
+    /// Inverse of `aes_dec` with the same key
+    #[inline]
+    fn inv_aes_dec(self, key: Self) -> Self {
+        (self ^ key).mix_columns().aes_enc_last(Self::default())
+    }
+
+    /// Inverse of `aes_dec_last` with the same key
+    #[inline]
+    fn inv_aes_dec_last(self, key: Self) -> Self {
+        (self ^ key).aes_enc_last(Self::default())
+    }
+
+    /// Inverse of `rounds` `aes_enc` rounds with the same key.
+    ///
+    /// Runs one InvMixColumns and `rounds` rounds on the state rather than
+    /// `inv_aes_enc` `rounds` times: each InvSubBytes/InvShiftRows is merged
+    /// with the following InvMixColumns into `aes_dec`, with the key passed
+    /// through InvMixColumns.
+    #[inline]
+    fn inv_aes_enc_rounds(self, key: Self, rounds: usize) -> Self {
+        if rounds == 0 {
+            return self;
+        }
+        let zero = Self::default();
+        // Skipped for zero keys, which get constant folded
+        let inv_mixed_key = if key == zero { zero } else { key.inv_mix_columns() };
+        let mut state = (self ^ key).inv_mix_columns();
+        for _ in 1..rounds {
+            state = state.aes_dec(inv_mixed_key);
+        }
+        state.aes_dec_last(zero)
+    }
+
+    /// Inverse of `rounds` `aes_dec` rounds with the same key.
+    ///
+    /// Runs one MixColumns and `rounds` rounds on the state, like
+    /// `inv_aes_enc_rounds`.
+    #[inline]
+    fn inv_aes_dec_rounds(self, key: Self, rounds: usize) -> Self {
+        if rounds == 0 {
+            return self;
+        }
+        let zero = Self::default();
+        // Skipped for zero keys, which get constant folded
+        let mixed_key = if key == zero { zero } else { key.mix_columns() };
+        let mut state = (self ^ key).mix_columns();
+        for _ in 1..rounds {
+            state = state.aes_enc(mixed_key);
+        }
+        state.aes_enc_last(zero)
+    }
+}
+
+/// Byte shuffle for ShiftRows: `out[i] = in[SHIFT_ROWS[i]]`
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const SHIFT_ROWS: [u8; 16] = [0, 5, 10, 15, 4, 9, 14, 3, 8, 13, 2, 7, 12, 1, 6, 11];
+/// Byte shuffle for InvShiftRows
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const INV_SHIFT_ROWS: [u8; 16] = [0, 13, 10, 7, 4, 1, 14, 11, 8, 5, 2, 15, 12, 9, 6, 3];
+
+#[cfg(target_arch = "x86_64")]
+mod aesni {
+    use core::arch::x86_64::{
+        _mm_aesenc_si128,
+        _mm_aesenclast_si128,
+        _mm_aesdec_si128,
+        _mm_aesdeclast_si128,
+        _mm_aesimc_si128,
+        _mm_shuffle_epi8,
+    };
+    use super::{Block, SHIFT_ROWS, INV_SHIFT_ROWS};
+
+    const ZERO: Block = Block::ZERO;
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn shift_rows(block: Block) -> Block {
+        Block(_mm_shuffle_epi8(block.0, Block::from_array(SHIFT_ROWS).0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn inv_shift_rows(block: Block) -> Block {
+        Block(_mm_shuffle_epi8(block.0, Block::from_array(INV_SHIFT_ROWS).0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes,ssse3")]
+    pub unsafe fn sub_bytes(block: Block) -> Block {
+        inv_shift_rows(Block(_mm_aesenclast_si128(block.0, ZERO.0)))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes,ssse3")]
+    pub unsafe fn inv_sub_bytes(block: Block) -> Block {
+        Block(_mm_aesdeclast_si128(shift_rows(block).0, ZERO.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn mix_columns(block: Block) -> Block {
+        Block(_mm_aesenc_si128(_mm_aesdeclast_si128(block.0, ZERO.0), ZERO.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn inv_mix_columns(block: Block) -> Block {
+        Block(_mm_aesimc_si128(block.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_enc(block: Block, key: Block) -> Block {
+        Block(_mm_aesenc_si128(block.0, key.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_enc_last(block: Block, key: Block) -> Block {
+        Block(_mm_aesenclast_si128(block.0, key.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_dec(block: Block, key: Block) -> Block {
+        Block(_mm_aesdec_si128(block.0, key.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_dec_last(block: Block, key: Block) -> Block {
+        Block(_mm_aesdeclast_si128(block.0, key.0))
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
-impl<T, const N: usize> SimdAes for Simd<T, N>
-where LaneCount<N>: SupportedLaneCount,
-      T: SimdElement,
-      Simd<T, N>: Into::<__m128i>,
-      Simd<T, N>: From::<__m128i>
-{
+impl SimdAes for Block {
+    #[inline]
+    fn sub_bytes(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::sub_bytes(self);
+        }
+        unsafe { aesni::sub_bytes(self) }
+    }
+
+    #[inline]
+    fn inv_sub_bytes(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::inv_sub_bytes(self);
+        }
+        unsafe { aesni::inv_sub_bytes(self) }
+    }
+
+    #[inline]
+    fn shift_rows(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::shift_rows(self);
+        }
+        unsafe { aesni::shift_rows(self) }
+    }
+
+    #[inline]
+    fn inv_shift_rows(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::inv_shift_rows(self);
+        }
+        unsafe { aesni::inv_shift_rows(self) }
+    }
+
+    #[inline]
+    fn mix_columns(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::mix_columns(self);
+        }
+        unsafe { aesni::mix_columns(self) }
+    }
+
+    #[inline]
+    fn inv_mix_columns(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::inv_mix_columns(self);
+        }
+        unsafe { aesni::inv_mix_columns(self) }
+    }
+
     #[inline]
     fn aes_enc(self, key: Self) -> Self {
-        unsafe { Self::from(_mm_aesenc_si128(self.into(), key.into())) }
+        if !has_hardware_aes() {
+            return soft::aes_enc(self, key);
+        }
+        unsafe { aesni::aes_enc(self, key) }
     }
 
     #[inline]
     fn aes_enc_last(self, key: Self) -> Self {
-        unsafe { Self::from(_mm_aesenclast_si128(self.into(), key.into())) }
+        if !has_hardware_aes() {
+            return soft::aes_enc_last(self, key);
+        }
+        unsafe { aesni::aes_enc_last(self, key) }
     }
 
     #[inline]
     fn aes_dec(self, key: Self) -> Self {
-        unsafe { Self::from(_mm_aesdec_si128(self.into(), key.into())) }
+        if !has_hardware_aes() {
+            return soft::aes_dec(self, key);
+        }
+        unsafe { aesni::aes_dec(self, key) }
     }
 
     #[inline]
     fn aes_dec_last(self, key: Self) -> Self {
-        unsafe { Self::from(_mm_aesdeclast_si128(self.into(), key.into())) }
+        if !has_hardware_aes() {
+            return soft::aes_dec_last(self, key);
+        }
+        unsafe { aesni::aes_dec_last(self, key) }
     }
 }
 
+/// The ARM instructions xor the key into the state before the round, while
+/// the x86 ones xor it after. The rounds here run with a zero key and xor the
+/// key afterwards, so both architectures compute the same function.
 #[cfg(target_arch = "aarch64")]
-use core::arch::aarch64::{
-    // uint8x16_t,
-    vld1q_u8,
-    vst1q_u8,
-    vaesdq_u8,
-    vaeseq_u8,
-    vaesimcq_u8,
-    vaesmcq_u8,
-};
+mod arm_crypto {
+    use core::arch::aarch64::{
+        vaesdq_u8,
+        vaeseq_u8,
+        vaesimcq_u8,
+        vaesmcq_u8,
+        vqtbl1q_u8,
+    };
+    use super::{Block, SHIFT_ROWS, INV_SHIFT_ROWS};
+
+    const ZERO: Block = Block::ZERO;
+
+    #[inline]
+    pub unsafe fn shift_rows(block: Block) -> Block {
+        Block(vqtbl1q_u8(block.0, Block::from_array(SHIFT_ROWS).0))
+    }
+
+    #[inline]
+    pub unsafe fn inv_shift_rows(block: Block) -> Block {
+        Block(vqtbl1q_u8(block.0, Block::from_array(INV_SHIFT_ROWS).0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn sub_bytes(block: Block) -> Block {
+        inv_shift_rows(Block(vaeseq_u8(block.0, ZERO.0)))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn inv_sub_bytes(block: Block) -> Block {
+        Block(vaesdq_u8(shift_rows(block).0, ZERO.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn mix_columns(block: Block) -> Block {
+        Block(vaesmcq_u8(block.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn inv_mix_columns(block: Block) -> Block {
+        Block(vaesimcq_u8(block.0))
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_enc(block: Block, key: Block) -> Block {
+        Block(vaesmcq_u8(vaeseq_u8(block.0, ZERO.0))) ^ key
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_enc_last(block: Block, key: Block) -> Block {
+        Block(vaeseq_u8(block.0, ZERO.0)) ^ key
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_dec(block: Block, key: Block) -> Block {
+        Block(vaesimcq_u8(vaesdq_u8(block.0, ZERO.0))) ^ key
+    }
+
+    #[inline]
+    #[target_feature(enable = "aes")]
+    pub unsafe fn aes_dec_last(block: Block, key: Block) -> Block {
+        Block(vaesdq_u8(block.0, ZERO.0)) ^ key
+    }
+}
 
 #[cfg(target_arch = "aarch64")]
-impl SimdAes for Simd<u8, 16> {
+impl SimdAes for Block {
+    #[inline]
+    fn sub_bytes(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::sub_bytes(self);
+        }
+        unsafe { arm_crypto::sub_bytes(self) }
+    }
+
+    #[inline]
+    fn inv_sub_bytes(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::inv_sub_bytes(self);
+        }
+        unsafe { arm_crypto::inv_sub_bytes(self) }
+    }
+
     #[inline]
     fn shift_rows(self) -> Self {
-        // AES state as 4x4 byte matrix (row-major):
-        // [ 0  1  2  3 ]
-        // [ 4  5  6  7 ]
-        // [ 8  9 10 11 ]
-        // [12 13 14 15 ]
-        // After ShiftRows:
-        // [ 0  1  2  3 ]  (no shift)
-        // [ 5  6  7  4 ]  (left shift by 1)
-        // [10 11  8  9 ]  (left shift by 2)
-        // [15 12 13 14 ]  (left shift by 3)
-        let indices = Simd::from_array([
-            0, 1, 2, 3,    // Row 0
-            5, 6, 7, 4,    // Row 1
-            10, 11, 8, 9,  // Row 2
-            15, 12, 13, 14 // Row 3
-        ]);
-        self.swizzle_dyn(indices)
+        if !has_hardware_aes() {
+            return soft::shift_rows(self);
+        }
+        unsafe { arm_crypto::shift_rows(self) }
     }
 
     #[inline]
     fn inv_shift_rows(self) -> Self {
-        // Inverse ShiftRows:
-        // [ 0  1  2  3 ]  (no shift)
-        // [ 7  4  5  6 ]  (right shift by 1 = left shift by 3)
-        // [10 11  8  9 ]  (right shift by 2 = left shift by 2)
-        // [13 14 15 12 ]  (right shift by 3 = left shift by 1)
-        let indices = Simd::from_array([
-            0, 1, 2, 3,    // Row 0
-            7, 4, 5, 6,    // Row 1
-            10, 11, 8, 9,  // Row 2
-            13, 14, 15, 12 // Row 3
-        ]);
-        self.swizzle_dyn(indices)
+        if !has_hardware_aes() {
+            return soft::inv_shift_rows(self);
+        }
+        unsafe { arm_crypto::inv_shift_rows(self) }
+    }
+
+    #[inline]
+    fn mix_columns(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::mix_columns(self);
+        }
+        unsafe { arm_crypto::mix_columns(self) }
+    }
+
+    #[inline]
+    fn inv_mix_columns(self) -> Self {
+        if !has_hardware_aes() {
+            return soft::inv_mix_columns(self);
+        }
+        unsafe { arm_crypto::inv_mix_columns(self) }
     }
 
     #[inline]
     fn aes_enc(self, key: Self) -> Self {
-        unsafe {
-            let state = vld1q_u8(self.as_array().as_ptr());
-            let key = vld1q_u8(key.as_array().as_ptr());
-            let result = vaesmcq_u8(vaeseq_u8(state, key));
-            let mut output = [0u8; 16];
-            vst1q_u8(output.as_mut_ptr(), result);
-            Simd::from_array(output)
+        if !has_hardware_aes() {
+            return soft::aes_enc(self, key);
         }
+        unsafe { arm_crypto::aes_enc(self, key) }
     }
 
     #[inline]
     fn aes_enc_last(self, key: Self) -> Self {
-        unsafe {
-            let state = vld1q_u8(self.as_array().as_ptr());
-            let key = vld1q_u8(key.as_array().as_ptr());
-            let result = vaeseq_u8(state, key);
-            let mut output = [0u8; 16];
-            vst1q_u8(output.as_mut_ptr(), result);
-            Simd::from_array(output)
+        if !has_hardware_aes() {
+            return soft::aes_enc_last(self, key);
         }
+        unsafe { arm_crypto::aes_enc_last(self, key) }
     }
 
     #[inline]
     fn aes_dec(self, key: Self) -> Self {
-        unsafe {
-            let state = vld1q_u8(self.as_array().as_ptr());
-            let key = vld1q_u8(key.as_array().as_ptr());
-            let result = vaesimcq_u8(vaesdq_u8(state, key));
-            let mut output = [0u8; 16];
-            vst1q_u8(output.as_mut_ptr(), result);
-            Simd::from_array(output)
+        if !has_hardware_aes() {
+            return soft::aes_dec(self, key);
         }
+        unsafe { arm_crypto::aes_dec(self, key) }
     }
 
     #[inline]
     fn aes_dec_last(self, key: Self) -> Self {
-        unsafe {
-            let state = vld1q_u8(self.as_array().as_ptr());
-            let key = vld1q_u8(key.as_array().as_ptr());
-            let result = vaesdq_u8(state, key);
-            let mut output = [0u8; 16];
-            vst1q_u8(output.as_mut_ptr(), result);
-            Simd::from_array(output)
+        if !has_hardware_aes() {
+            return soft::aes_dec_last(self, key);
+        }
+        unsafe { arm_crypto::aes_dec_last(self, key) }
+    }
+}
+
+/// `SimdAes` for `core::simd` vectors of 128, 256 or 512 bits, going
+/// through `Block`, `Block2` or `Block4`
+#[cfg(feature = "nightly")]
+mod nightly {
+    use core::ops::BitXor;
+    use core::simd::{Simd, SimdElement, ToBytes};
+    use super::{Block, SimdAes};
+
+    /// The byte vectors of the `core::simd` types that implement `SimdAes`,
+    /// with the block type of the same size
+    pub trait SimdBlocks: Copy {
+        /// `Block`, `Block2` or `Block4`
+        type Blocks: SimdAes;
+        fn to_blocks(self) -> Self::Blocks;
+        fn from_blocks(blocks: Self::Blocks) -> Self;
+    }
+
+    impl SimdBlocks for Simd<u8, 16> {
+        type Blocks = Block;
+
+        #[inline]
+        fn to_blocks(self) -> Block {
+            Block::from(self)
+        }
+
+        #[inline]
+        fn from_blocks(blocks: Block) -> Self {
+            Self::from(blocks)
         }
     }
-}
\ No newline at end of file
+
+    /// Apply `f` to the blocks of `value`
+    #[inline]
+    fn map<V, F>(value: V, f: F) -> V
+    where
+        V: ToBytes<Bytes: SimdBlocks>,
+        F: FnOnce(<V::Bytes as SimdBlocks>::Blocks) -> <V::Bytes as SimdBlocks>::Blocks,
+    {
+        V::from_ne_bytes(SimdBlocks::from_blocks(f(value.to_ne_bytes().to_blocks())))
+    }
+
+    impl<T, const N: usize> SimdAes for Simd<T, N>
+    where
+        T: SimdElement + Default + PartialEq,
+        Simd<T, N>: BitXor<Output = Simd<T, N>>,
+        Simd<T, N>: ToBytes<Bytes: SimdBlocks>,
+    {
+        #[inline]
+        fn sub_bytes(self) -> Self {
+            map(self, SimdAes::sub_bytes)
+        }
+
+        #[inline]
+        fn inv_sub_bytes(self) -> Self {
+            map(self, SimdAes::inv_sub_bytes)
+        }
+
+        #[inline]
+        fn shift_rows(self) -> Self {
+            map(self, SimdAes::shift_rows)
+        }
+
+        #[inline]
+        fn inv_shift_rows(self) -> Self {
+            map(self, SimdAes::inv_shift_rows)
+        }
+
+        #[inline]
+        fn mix_columns(self) -> Self {
+            map(self, SimdAes::mix_columns)
+        }
+
+        #[inline]
+        fn inv_mix_columns(self) -> Self {
+            map(self, SimdAes::inv_mix_columns)
+        }
+
+        #[inline]
+        fn aes_enc(self, key: Self) -> Self {
+            let key = key.to_ne_bytes().to_blocks();
+            map(self, |blocks| blocks.aes_enc(key))
+        }
+
+        #[inline]
+        fn aes_enc_last(self, key: Self) -> Self {
+            let key = key.to_ne_bytes().to_blocks();
+            map(self, |blocks| blocks.aes_enc_last(key))
+        }
+
+        #[inline]
+        fn aes_dec(self, key: Self) -> Self {
+            let key = key.to_ne_bytes().to_blocks();
+            map(self, |blocks| blocks.aes_dec(key))
+        }
+
+        #[inline]
+        fn aes_dec_last(self, key: Self) -> Self {
+            let key = key.to_ne_bytes().to_blocks();
+            map(self, |blocks| blocks.aes_dec_last(key))
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub use nightly::SimdBlocks;
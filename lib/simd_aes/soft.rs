@@ -0,0 +1,243 @@
+//! Portable software AES rounds, for targets without AES instructions.
+//!
+//! Table-free: the S-box is computed with `gf256::sbox` as the inverse
+//! followed by the affine map, and field multiplication is branch-free, so
+//! there are no secret-dependent memory accesses.
+
+use core::ops::BitXor;
+use super::{Block, SimdAes};
+use super::gf256::{inv_sbox, mul, sbox, xtime};
+
+/// `Block` using the software rounds regardless of the target features
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Soft(pub Block);
+
+impl BitXor for Soft {
+    type Output = Soft;
+
+    #[inline]
+    fn bitxor(self, rhs: Soft) -> Soft {
+        Soft(self.0 ^ rhs.0)
+    }
+}
+
+// The state is column-major: byte `i` is row `i % 4` of column `i / 4`.
+
+/// SubBytes
+#[inline]
+pub fn sub_bytes(state: Block) -> Block {
+    Block::from_array(state.to_array().map(sbox))
+}
+
+/// InvSubBytes
+#[inline]
+pub fn inv_sub_bytes(state: Block) -> Block {
+    Block::from_array(state.to_array().map(inv_sbox))
+}
+
+/// ShiftRows
+#[inline]
+pub fn shift_rows(state: Block) -> Block {
+    let state = state.to_array();
+    Block::from_array(core::array::from_fn(|ii| state[(ii + 4 * (ii % 4)) % 16]))
+}
+
+/// InvShiftRows
+#[inline]
+pub fn inv_shift_rows(state: Block) -> Block {
+    let state = state.to_array();
+    Block::from_array(core::array::from_fn(|ii| state[(ii + 16 - 4 * (ii % 4)) % 16]))
+}
+
+/// MixColumns
+#[inline]
+pub fn mix_columns(state: Block) -> Block {
+    let state = state.to_array();
+    let mut out = [0; 16];
+    for (column, out) in state.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        for row in 0..4 {
+            let a0 = column[row];
+            let a1 = column[(row + 1) % 4];
+            let a2 = column[(row + 2) % 4];
+            let a3 = column[(row + 3) % 4];
+            out[row] = xtime(a0) ^ xtime(a1) ^ a1 ^ a2 ^ a3;
+        }
+    }
+    Block::from_array(out)
+}
+
+/// InvMixColumns
+#[inline]
+pub fn inv_mix_columns(state: Block) -> Block {
+    let state = state.to_array();
+    let mut out = [0; 16];
+    for (column, out) in state.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        for row in 0..4 {
+            let a0 = column[row];
+            let a1 = column[(row + 1) % 4];
+            let a2 = column[(row + 2) % 4];
+            let a3 = column[(row + 3) % 4];
+            out[row] = mul(a0, 14) ^ mul(a1, 11) ^ mul(a2, 13) ^ mul(a3, 9);
+        }
+    }
+    Block::from_array(out)
+}
+
+/// One AES encryption round, same as `aesenc`
+#[inline]
+pub fn aes_enc(state: Block, key: Block) -> Block {
+    mix_columns(shift_rows(sub_bytes(state))) ^ key
+}
+
+/// The last AES encryption round, same as `aesenclast`
+#[inline]
+pub fn aes_enc_last(state: Block, key: Block) -> Block {
+    shift_rows(sub_bytes(state)) ^ key
+}
+
+/// One AES decryption round, same as `aesdec`
+#[inline]
+pub fn aes_dec(state: Block, key: Block) -> Block {
+    inv_mix_columns(inv_sub_bytes(inv_shift_rows(state))) ^ key
+}
+
+/// The last AES decryption round, same as `aesdeclast`
+#[inline]
+pub fn aes_dec_last(state: Block, key: Block) -> Block {
+    inv_sub_bytes(inv_shift_rows(state)) ^ key
+}
+
+impl SimdAes for Soft {
+    #[inline]
+    fn sub_bytes(self) -> Self {
+        Soft(sub_bytes(self.0))
+    }
+
+    #[inline]
+    fn inv_sub_bytes(self) -> Self {
+        Soft(inv_sub_bytes(self.0))
+    }
+
+    #[inline]
+    fn shift_rows(self) -> Self {
+        Soft(shift_rows(self.0))
+    }
+
+    #[inline]
+    fn inv_shift_rows(self) -> Self {
+        Soft(inv_shift_rows(self.0))
+    }
+
+    #[inline]
+    fn mix_columns(self) -> Self {
+        Soft(mix_columns(self.0))
+    }
+
+    #[inline]
+    fn inv_mix_columns(self) -> Self {
+        Soft(inv_mix_columns(self.0))
+    }
+
+
+    #[inline]
+    fn aes_enc(self, key: Self) -> Self {
+        Soft(aes_enc(self.0, key.0))
+    }
+
+    #[inline]
+    fn aes_enc_last(self, key: Self) -> Self {
+        Soft(aes_enc_last(self.0, key.0))
+    }
+
+    #[inline]
+    fn aes_dec(self, key: Self) -> Self {
+        Soft(aes_dec(self.0, key.0))
+    }
+
+    #[inline]
+    fn aes_dec_last(self, key: Self) -> Self {
+        Soft(aes_dec_last(self.0, key.0))
+    }
+}
+
+/// Without AES instructions in the architecture, `Block` uses the software
+/// rounds
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl SimdAes for Block {
+    #[inline]
+    fn sub_bytes(self) -> Self {
+        sub_bytes(self)
+    }
+
+    #[inline]
+    fn inv_sub_bytes(self) -> Self {
+        inv_sub_bytes(self)
+    }
+
+    #[inline]
+    fn shift_rows(self) -> Self {
+        shift_rows(self)
+    }
+
+    #[inline]
+    fn inv_shift_rows(self) -> Self {
+        inv_shift_rows(self)
+    }
+
+    #[inline]
+    fn mix_columns(self) -> Self {
+        mix_columns(self)
+    }
+
+    #[inline]
+    fn inv_mix_columns(self) -> Self {
+        inv_mix_columns(self)
+    }
+
+    #[inline]
+    fn aes_enc(self, key: Self) -> Self {
+        aes_enc(self, key)
+    }
+
+    #[inline]
+    fn aes_enc_last(self, key: Self) -> Self {
+        aes_enc_last(self, key)
+    }
+
+    #[inline]
+    fn aes_dec(self, key: Self) -> Self {
+        aes_dec(self, key)
+    }
+
+    #[inline]
+    fn aes_dec_last(self, key: Self) -> Self {
+        aes_dec_last(self, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::blocks;
+
+    #[test]
+    fn last_rounds_invert() {
+        let zero = Block::ZERO;
+        for block in blocks(1000) {
+            assert_eq!(aes_dec_last(aes_enc_last(block, zero), zero), block);
+            assert_eq!(aes_enc_last(aes_dec_last(block, zero), zero), block);
+        }
+    }
+
+    /// Compares against `Block`, which uses the hardware rounds when the
+    /// CPU has them
+    #[test]
+    fn matches_block() {
+        for (block, key) in blocks(1000).zip(blocks(1001).skip(1)) {
+            assert_eq!(aes_enc(block, key), block.aes_enc(key));
+            assert_eq!(aes_enc_last(block, key), block.aes_enc_last(key));
+            assert_eq!(aes_dec(block, key), block.aes_dec(key));
+            assert_eq!(aes_dec_last(block, key), block.aes_dec_last(key));
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Fixtures shared by the tests of this crate
+
+use super::{Block, Block4};
+
+/// `count` deterministic, different test blocks from an xorshift generator
+pub fn blocks(count: usize) -> impl Iterator<Item = Block> {
+    let mut state = 0x9e3779b97f4a7c15_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count).map(move |_| Block::from_u64s([next(), next()]))
+}
+
+/// `count` test blocks with a different block in every lane
+pub fn blocks4(count: usize) -> impl Iterator<Item = Block4> {
+    let mut blocks = blocks(4 * count);
+    (0..count).map(move |_| Block4(core::array::from_fn(|_| blocks.next().unwrap())))
+}
@@ -0,0 +1,330 @@
+//! Several independent blocks in one wide vector, one per 128-bit lane.
+//!
+//! With VAES the rounds run on all lanes in one instruction; otherwise they
+//! fall back to narrower VAES or to the `Block` rounds lane by lane. The step
+//! functions always work lane by lane.
+
+use core::ops::{BitXor, BitXorAssign};
+use super::{Block, SimdAes};
+#[cfg(target_arch = "x86_64")]
+use super::backend::{has_vaes256, has_vaes512};
+
+macro_rules! wide_block {
+    ($name:ident, $lanes:literal, $align:literal, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        #[repr(C, align($align))]
+        pub struct $name(pub [Block; $lanes]);
+
+        impl $name {
+            /// Number of blocks
+            pub const LANES: usize = $lanes;
+
+            /// All-zero blocks
+            pub const ZERO: $name = $name([Block::ZERO; $lanes]);
+
+            /// The same block in every lane
+            #[inline]
+            pub const fn splat(block: Block) -> Self {
+                $name([block; $lanes])
+            }
+
+            /// Apply `f` to every lane
+            #[inline]
+            fn map(self, f: impl Fn(Block) -> Block) -> Self {
+                $name(self.0.map(f))
+            }
+
+            /// Apply `f` to every lane of `self` and `other`
+            #[inline]
+            fn zip(self, other: Self, f: impl Fn(Block, Block) -> Block) -> Self {
+                $name(core::array::from_fn(|ii| f(self.0[ii], other.0[ii])))
+            }
+        }
+
+        impl From<[Block; $lanes]> for $name {
+            fn from(blocks: [Block; $lanes]) -> Self {
+                $name(blocks)
+            }
+        }
+
+        impl From<$name> for [Block; $lanes] {
+            fn from(blocks: $name) -> Self {
+                blocks.0
+            }
+        }
+
+        impl BitXor for $name {
+            type Output = $name;
+
+            #[inline]
+            fn bitxor(self, rhs: $name) -> $name {
+                self.zip(rhs, BitXor::bitxor)
+            }
+        }
+
+        impl BitXorAssign for $name {
+            #[inline]
+            fn bitxor_assign(&mut self, rhs: $name) {
+                *self = *self ^ rhs;
+            }
+        }
+    };
+}
+
+wide_block!(Block2, 2, 32, "Two blocks, a 256-bit vector");
+wide_block!(Block4, 4, 64, "Four blocks, a 512-bit vector");
+
+impl Block4 {
+    /// The lower and upper halves
+    #[inline]
+    pub(crate) fn halves(self) -> [Block2; 2] {
+        let [a, b, c, d] = self.0;
+        [Block2([a, b]), Block2([c, d])]
+    }
+
+    /// Join halves from `halves`
+    #[inline]
+    pub(crate) fn from_halves([low, high]: [Block2; 2]) -> Self {
+        let ([a, b], [c, d]) = (low.0, high.0);
+        Block4([a, b, c, d])
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod vaes {
+    use core::arch::x86_64::{
+        __m256i,
+        __m512i,
+        _mm256_aesdec_epi128,
+        _mm256_aesdeclast_epi128,
+        _mm256_aesenc_epi128,
+        _mm256_aesenclast_epi128,
+        _mm512_aesdec_epi128,
+        _mm512_aesdeclast_epi128,
+        _mm512_aesenc_epi128,
+        _mm512_aesenclast_epi128,
+    };
+    use core::mem::transmute;
+    use super::{Block2, Block4};
+
+    macro_rules! vaes_round {
+        ($name:ident, $ty:ident, $vector:ident, $intrinsic:ident, $features:literal) => {
+            #[inline]
+            #[target_feature(enable = $features)]
+            pub unsafe fn $name(block: $ty, key: $ty) -> $ty {
+                // SAFETY: the wide blocks are plain bytes of the vector size.
+                let (block, key) = (transmute::<$ty, $vector>(block), transmute::<$ty, $vector>(key));
+                transmute::<$vector, $ty>($intrinsic(block, key))
+            }
+        };
+    }
+
+    vaes_round!(aes_enc2, Block2, __m256i, _mm256_aesenc_epi128, "avx2,vaes");
+    vaes_round!(aes_enc_last2, Block2, __m256i, _mm256_aesenclast_epi128, "avx2,vaes");
+    vaes_round!(aes_dec2, Block2, __m256i, _mm256_aesdec_epi128, "avx2,vaes");
+    vaes_round!(aes_dec_last2, Block2, __m256i, _mm256_aesdeclast_epi128, "avx2,vaes");
+    vaes_round!(aes_enc4, Block4, __m512i, _mm512_aesenc_epi128, "avx512f,vaes");
+    vaes_round!(aes_enc_last4, Block4, __m512i, _mm512_aesenclast_epi128, "avx512f,vaes");
+    vaes_round!(aes_dec4, Block4, __m512i, _mm512_aesdec_epi128, "avx512f,vaes");
+    vaes_round!(aes_dec_last4, Block4, __m512i, _mm512_aesdeclast_epi128, "avx512f,vaes");
+}
+
+/// The step functions, lane by lane
+macro_rules! lane_steps {
+    () => {
+        #[inline]
+        fn sub_bytes(self) -> Self {
+            self.map(Block::sub_bytes)
+        }
+
+        #[inline]
+        fn inv_sub_bytes(self) -> Self {
+            self.map(Block::inv_sub_bytes)
+        }
+
+        #[inline]
+        fn shift_rows(self) -> Self {
+            self.map(Block::shift_rows)
+        }
+
+        #[inline]
+        fn inv_shift_rows(self) -> Self {
+            self.map(Block::inv_shift_rows)
+        }
+
+        #[inline]
+        fn mix_columns(self) -> Self {
+            self.map(Block::mix_columns)
+        }
+
+        #[inline]
+        fn inv_mix_columns(self) -> Self {
+            self.map(Block::inv_mix_columns)
+        }
+    };
+}
+
+impl SimdAes for Block2 {
+    lane_steps!();
+
+    #[inline]
+    fn aes_enc(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes256() {
+            return unsafe { vaes::aes_enc2(self, key) };
+        }
+        self.zip(key, Block::aes_enc)
+    }
+
+    #[inline]
+    fn aes_enc_last(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes256() {
+            return unsafe { vaes::aes_enc_last2(self, key) };
+        }
+        self.zip(key, Block::aes_enc_last)
+    }
+
+    #[inline]
+    fn aes_dec(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes256() {
+            return unsafe { vaes::aes_dec2(self, key) };
+        }
+        self.zip(key, Block::aes_dec)
+    }
+
+    #[inline]
+    fn aes_dec_last(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes256() {
+            return unsafe { vaes::aes_dec_last2(self, key) };
+        }
+        self.zip(key, Block::aes_dec_last)
+    }
+}
+
+/// Without 512-bit VAES, `Block4` runs as two `Block2`
+impl SimdAes for Block4 {
+    lane_steps!();
+
+    #[inline]
+    fn aes_enc(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes512() {
+            return unsafe { vaes::aes_enc4(self, key) };
+        }
+        let ([a, b], [c, d]) = (self.halves(), key.halves());
+        Block4::from_halves([a.aes_enc(c), b.aes_enc(d)])
+    }
+
+    #[inline]
+    fn aes_enc_last(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes512() {
+            return unsafe { vaes::aes_enc_last4(self, key) };
+        }
+        let ([a, b], [c, d]) = (self.halves(), key.halves());
+        Block4::from_halves([a.aes_enc_last(c), b.aes_enc_last(d)])
+    }
+
+    #[inline]
+    fn aes_dec(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes512() {
+            return unsafe { vaes::aes_dec4(self, key) };
+        }
+        let ([a, b], [c, d]) = (self.halves(), key.halves());
+        Block4::from_halves([a.aes_dec(c), b.aes_dec(d)])
+    }
+
+    #[inline]
+    fn aes_dec_last(self, key: Self) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if has_vaes512() {
+            return unsafe { vaes::aes_dec_last4(self, key) };
+        }
+        let ([a, b], [c, d]) = (self.halves(), key.halves());
+        Block4::from_halves([a.aes_dec_last(c), b.aes_dec_last(d)])
+    }
+}
+
+/// Conversions from 256-bit and 512-bit `core::simd` vectors
+#[cfg(feature = "nightly")]
+mod nightly {
+    use core::simd::Simd;
+    use super::{Block2, Block4};
+    use crate::SimdBlocks;
+
+    macro_rules! simd_wide {
+        ($ty:ty, $wide:ident) => {
+            impl From<$ty> for $wide {
+                #[inline]
+                fn from(value: $ty) -> Self {
+                    // SAFETY: both are plain bytes of the same size.
+                    unsafe { core::mem::transmute::<$ty, $wide>(value) }
+                }
+            }
+
+            impl From<$wide> for $ty {
+                #[inline]
+                fn from(value: $wide) -> Self {
+                    // SAFETY: both are plain bytes of the same size.
+                    unsafe { core::mem::transmute::<$wide, $ty>(value) }
+                }
+            }
+
+            impl SimdBlocks for $ty {
+                type Blocks = $wide;
+
+                #[inline]
+                fn to_blocks(self) -> $wide {
+                    $wide::from(self)
+                }
+
+                #[inline]
+                fn from_blocks(blocks: $wide) -> Self {
+                    Self::from(blocks)
+                }
+            }
+        };
+    }
+
+    simd_wide!(Simd<u8, 32>, Block2);
+    simd_wide!(Simd<u8, 64>, Block4);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::blocks4;
+
+    fn low(block: Block4) -> Block2 {
+        block.halves()[0]
+    }
+
+    #[test]
+    fn block4_matches_lanes() {
+        for (block, key) in blocks4(250).zip(blocks4(251).skip(1)) {
+            let lanes = |f: fn(Block, Block) -> Block| block.zip(key, f);
+            assert_eq!(block.aes_enc(key), lanes(Block::aes_enc));
+            assert_eq!(block.aes_enc_last(key), lanes(Block::aes_enc_last));
+            assert_eq!(block.aes_dec(key), lanes(Block::aes_dec));
+            assert_eq!(block.aes_dec_last(key), lanes(Block::aes_dec_last));
+            assert_eq!(block.inv_aes_dec_rounds(key, 4), block.zip(key, |lane, key| lane.inv_aes_dec_rounds(key, 4)));
+        }
+    }
+
+    #[test]
+    fn block2_matches_lanes() {
+        for (block, key) in blocks4(250).map(low).zip(blocks4(251).skip(1).map(low)) {
+            let lanes = |f: fn(Block, Block) -> Block| block.zip(key, f);
+            assert_eq!(block.aes_enc(key), lanes(Block::aes_enc));
+            assert_eq!(block.aes_enc_last(key), lanes(Block::aes_enc_last));
+            assert_eq!(block.aes_dec(key), lanes(Block::aes_dec));
+            assert_eq!(block.aes_dec_last(key), lanes(Block::aes_dec_last));
+            assert_eq!(block.mix_columns(), block.map(Block::mix_columns));
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! A counter-based generator: AES-128 encryptions of a 128-bit counter.
+//!
+//! Uses the hardware rounds through `simd_aes` when the CPU has them.
+
+use simd_aes::{Aes128, Block};
+use super::{DEFAULT_LANES, Lanes, RandomSource, Width, splitmix_words};
+
+/// AES-128 in counter mode: the counter is the low half of the block, the
+/// stream index the high half
+#[derive(Clone)]
+pub struct AesCtr<const LANES: usize = DEFAULT_LANES> {
+    cipher: Aes128,
+    /// Stream index, so that streams of the same key never overlap
+    nonce: u64,
+    counter: u64,
+}
+
+impl<const LANES: usize> AesCtr<LANES> {
+    /// Construct with a given key and stream index
+    pub fn new(key: &[u8; 16], nonce: u64) -> Self {
+        Self { cipher: Aes128::new(key), nonce, counter: 0 }
+    }
+}
+
+impl<const LANES: usize> RandomSource for AesCtr<LANES>
+where
+    Width<LANES>: Lanes<Words = [u64; LANES]>,
+{
+    const NAME: &'static str = "aes-ctr";
+    type Width = Width<LANES>;
+
+    /// The key is expanded from `seed`, the stream index is the nonce
+    fn stream(seed: u64, index: u64) -> Self {
+        let [lo, hi] = splitmix_words(seed);
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&lo.to_le_bytes());
+        key[8..].copy_from_slice(&hi.to_le_bytes());
+        Self::new(&key, index)
+    }
+
+    #[inline]
+    fn next(&mut self) -> [u64; LANES] {
+        let mut result = [0; LANES];
+        for pair in result.chunks_exact_mut(2) {
+            let block = Block::from_u64s([self.counter, self.nonce]);
+            pair.copy_from_slice(&self.cipher.encrypt(block).to_u64s());
+            self.counter = self.counter.wrapping_add(1);
+        }
+        result
+    }
+}
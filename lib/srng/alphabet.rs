@@ -0,0 +1,119 @@
+//! Byte alphabets for the character helpers of `RandomSource`
+
+use super::fill::Chunk;
+
+/// A set of 1 to 256 distinct byte symbols, in a fixed order.
+///
+/// Random indices are mapped to symbols with a 256-entry lookup table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    /// The symbols, then zeros
+    table: [u8; 256],
+    /// Number of symbols
+    len: u32,
+    /// `2^16 mod len`: products `x * len` with a low half below this are
+    /// rejected for uniform sampling
+    threshold: u32,
+    /// How `Filler` takes digits of base `len`
+    chunk: Chunk,
+}
+
+impl Alphabet {
+    /// The printable ASCII characters, 0x20 to 0x7e
+    pub const ASCII: Alphabet = Alphabet::range(0x20, 0x7e);
+    /// `0-9A-Za-z`
+    pub const ALPHANUM: Alphabet =
+        Alphabet::new(b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz");
+    /// Base64 digits, in ASCII order
+    pub const B64: Alphabet =
+        Alphabet::new(b"+/0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz");
+    /// Url-safe base64 digits, in ASCII order
+    pub const UB64: Alphabet =
+        Alphabet::new(b"-0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz");
+    /// Lowercase hexadecimal digits
+    pub const HEX: Alphabet = Alphabet::new(b"0123456789abcdef");
+    /// Lowercase letters
+    pub const LOWERCASE: Alphabet = Alphabet::range(b'a', b'z');
+    /// Characters that never need quoting in a POSIX shell word
+    pub const SHELL_SAFE: Alphabet = Alphabet::new(
+        b"%+,-./0123456789:=@ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz");
+
+    /// An alphabet of the given symbols, in the given order.
+    ///
+    /// Panics if `symbols` is empty, longer than 256 or has duplicates.
+    pub const fn new(symbols: &[u8]) -> Alphabet {
+        assert!(!symbols.is_empty() && symbols.len() <= 256, "an alphabet has 1 to 256 symbols");
+        let mut seen = [false; 256];
+        let mut table = [0; 256];
+        let mut ii = 0;
+        while ii < symbols.len() {
+            let symbol = symbols[ii];
+            assert!(!seen[symbol as usize], "duplicate symbol in alphabet");
+            seen[symbol as usize] = true;
+            table[ii] = symbol;
+            ii += 1;
+        }
+        Alphabet::from_table(table, symbols.len() as u32)
+    }
+
+    /// The bytes `first..=last`
+    pub const fn range(first: u8, last: u8) -> Alphabet {
+        assert!(first <= last, "empty range");
+        let mut table = [0; 256];
+        let mut ii = 0;
+        while ii <= (last - first) as usize {
+            table[ii] = first + ii as u8;
+            ii += 1;
+        }
+        Alphabet::from_table(table, (last - first) as u32 + 1)
+    }
+
+    const fn from_table(table: [u8; 256], len: u32) -> Alphabet {
+        Alphabet { table, len, threshold: (1 << 16) % len, chunk: Chunk::new(len as u64) }
+    }
+
+    /// Number of symbols
+    #[allow(clippy::len_without_is_empty)]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// The symbols, in order
+    pub fn symbols(&self) -> &[u8] {
+        &self.table[..self.len()]
+    }
+
+    /// Whether `byte` is one of the symbols
+    pub fn contains(&self, byte: u8) -> bool {
+        self.symbols().contains(&byte)
+    }
+
+    /// The symbol at a 16-bit fraction `x / 2^16` of the alphabet
+    #[inline]
+    pub fn scale(&self, x: u16) -> u8 {
+        self.table[((x as u32 * self.len) >> 16) as usize]
+    }
+
+    /// Like `scale`, but `None` for the few `x` that make the mapping
+    /// non-uniform, from Lemire's "Fast Random Integer Generation in an
+    /// Interval"
+    #[inline]
+    pub fn scale_uniform(&self, x: u16) -> Option<u8> {
+        let product = x as u32 * self.len;
+        if product & 0xffff < self.threshold {
+            return None;
+        }
+        Some(self.table[(product >> 16) as usize])
+    }
+
+    /// How `Filler` takes digits of base `len`
+    pub(crate) fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
+    /// The symbol at `index`, which must be below 256
+    #[inline]
+    pub fn symbol(&self, index: u8) -> u8 {
+        self.table[index as usize]
+    }
+}
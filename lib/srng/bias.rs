@@ -0,0 +1,102 @@
+//! Per-symbol bias of character sampling, so that search-space estimates
+//! can use the entropy actually produced rather than `log2(len)`
+
+use core::fmt;
+use super::Alphabet;
+
+/// Symbol counts of a sampler over an alphabet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BiasReport {
+    /// Index of every byte in the alphabet, `u8::MAX` if it is not in it
+    index: [u8; 256],
+    /// Count of every symbol, in alphabet order
+    counts: Vec<u64>,
+}
+
+impl BiasReport {
+    /// An empty report for `alphabet`
+    pub fn new(alphabet: &Alphabet) -> Self {
+        let mut index = [u8::MAX; 256];
+        for (ii, &symbol) in alphabet.symbols().iter().enumerate() {
+            index[symbol as usize] = ii as u8;
+        }
+        Self { index, counts: vec![0; alphabet.len()] }
+    }
+
+    /// The exact bias of `Alphabet::scale`: every 16-bit input counted once
+    pub fn multiply_shift(alphabet: &Alphabet) -> Self {
+        let mut report = Self::new(alphabet);
+        for x in 0..=u16::MAX {
+            report.add(&[alphabet.scale(x)]);
+        }
+        report
+    }
+
+    /// Count the symbols of `rounds` calls of `sample`, e.g.
+    /// `|| rng.random_alphanum()`
+    pub fn measure<A: AsRef<[u8]>>(
+        alphabet: &Alphabet,
+        rounds: u64,
+        mut sample: impl FnMut() -> A,
+    ) -> Self {
+        let mut report = Self::new(alphabet);
+        for _ in 0..rounds {
+            report.add(sample().as_ref());
+        }
+        report
+    }
+
+    /// Count `symbols`. Panics on bytes outside the alphabet.
+    pub fn add(&mut self, symbols: &[u8]) {
+        for &symbol in symbols {
+            let index = self.index[symbol as usize] as usize;
+            // `u8::MAX` is only a valid index in a full 256-symbol alphabet
+            assert!(index < self.counts.len(), "{symbol:#x} is not in the alphabet");
+            self.counts[index] += 1;
+        }
+    }
+
+    /// Count of every symbol, in alphabet order
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Total number of symbols counted
+    pub fn samples(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Largest relative deviation of a symbol's frequency from uniform,
+    /// e.g. 0.01 if some symbol is 1% more or less likely than it should be
+    pub fn max_bias(&self) -> f64 {
+        let expected = self.samples() as f64 / self.counts.len() as f64;
+        self.counts.iter()
+            .map(|&count| (count as f64 / expected - 1.0).abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Shannon entropy of the observed frequencies, in bits per symbol
+    pub fn entropy_bits(&self) -> f64 {
+        let samples = self.samples() as f64;
+        self.counts.iter()
+            .filter(|&&count| count != 0)
+            .map(|&count| {
+                let p = count as f64 / samples;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Entropy of a uniform choice from the alphabet, in bits per symbol
+    pub fn uniform_bits(&self) -> f64 {
+        (self.counts.len() as f64).log2()
+    }
+}
+
+impl fmt::Display for BiasReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} symbols, {} samples, max bias {:.4}%, {:.6} of {:.6} bits/symbol",
+            self.counts.len(), self.samples(), self.max_bias() * 100.0,
+            self.entropy_bits(), self.uniform_bits())
+    }
+}
@@ -0,0 +1,118 @@
+//! Seeds from operating system entropy, for runs that should not repeat
+//! each other.
+//!
+//! Uses `getrandom(2)` on Linux and `/dev/urandom` on other unix systems,
+//! and falls back to a seed derived from the time, process id and a
+//! per-process counter when neither is available.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::{GOLDEN_GAMMA, splitmix64};
+
+/// Where `fill_entropy` got its bytes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntropySource {
+    /// The `getrandom(2)` system call
+    GetRandom,
+    /// `/dev/urandom`
+    DevUrandom,
+    /// Time, process id and a counter; unique, but not unpredictable
+    Fallback,
+}
+
+/// Fill `buf` with operating system entropy, or with the fallback seed
+/// material if there is none
+pub fn fill_entropy(buf: &mut [u8]) -> EntropySource {
+    #[cfg(target_os = "linux")]
+    if getrandom(buf) {
+        return EntropySource::GetRandom;
+    }
+    #[cfg(unix)]
+    if dev_urandom(buf) {
+        return EntropySource::DevUrandom;
+    }
+    fallback(buf);
+    EntropySource::Fallback
+}
+
+/// A 64-bit seed from `fill_entropy`
+pub fn entropy_seed() -> u64 {
+    let mut bytes = [0; 8];
+    fill_entropy(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(target_os = "linux")]
+fn getrandom(buf: &mut [u8]) -> bool {
+    extern "C" {
+        fn getrandom(buf: *mut core::ffi::c_void, buflen: usize, flags: core::ffi::c_uint) -> isize;
+    }
+    let mut filled = 0;
+    while filled < buf.len() {
+        let rest = &mut buf[filled..];
+        // SAFETY: the pointer and length describe the writable `rest`.
+        let read = unsafe { getrandom(rest.as_mut_ptr().cast(), rest.len(), 0) };
+        if read < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return false;
+        }
+        filled += read as usize;
+    }
+    true
+}
+
+#[cfg(unix)]
+fn dev_urandom(buf: &mut [u8]) -> bool {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut file| file.read_exact(buf))
+        .is_ok()
+}
+
+/// Calls of `fallback` in this process, so consecutive seeds differ even
+/// within one clock tick
+static FALLBACK_CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Seed material from the time, process id, a stack address and a counter,
+/// expanded with SplitMix64
+fn fallback(buf: &mut [u8]) {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    let calls = FALLBACK_CALLS.fetch_add(1, Ordering::Relaxed);
+    let stack = &calls as *const u64 as u64;
+    let mut state = splitmix64(nanos)
+        ^ splitmix64(std::process::id() as u64 ^ stack.rotate_left(32))
+        ^ calls.wrapping_mul(GOLDEN_GAMMA);
+    for chunk in buf.chunks_mut(8) {
+        state = state.wrapping_add(GOLDEN_GAMMA);
+        chunk.copy_from_slice(&splitmix64(state).to_le_bytes()[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_differ() {
+        assert_ne!(entropy_seed(), entropy_seed());
+
+        let (mut first, mut second) = ([0; 20], [0; 20]);
+        fallback(&mut first);
+        fallback(&mut second);
+        assert_ne!(first, second);
+    }
+
+    /// Assumes a unix system with `getrandom(2)` or a readable
+    /// `/dev/urandom`, whichever `fill_entropy` picks
+    #[cfg(unix)]
+    #[test]
+    fn uses_os_entropy() {
+        let (mut first, mut second) = ([0; 64], [0; 64]);
+        assert_ne!(fill_entropy(&mut first), EntropySource::Fallback);
+        assert_ne!(fill_entropy(&mut second), EntropySource::Fallback);
+        assert_ne!(first, second);
+    }
+}
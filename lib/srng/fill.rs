@@ -0,0 +1,246 @@
+//! Variable-length fills that keep the random bits left over by one call
+//! for the next
+
+use super::{Alphabet, LaneArray, Lanes, RandomSource, Words};
+
+/// Precision and symbol costs are counted in `2^-COST_SHIFT` bits
+const COST_SHIFT: u32 = 16;
+
+/// Bits of precision kept below a chunk of an alphabet whose length is not
+/// a power of two, so that rounding biases it by at most `2^-32`
+const MARGIN: u32 = 32 << COST_SHIFT;
+
+/// `log2(len)` in `2^-COST_SHIFT` bits, rounded up
+const fn cost(len: u64) -> u32 {
+    let whole = 63 - len.leading_zeros();
+    if len.is_power_of_two() {
+        return whole << COST_SHIFT;
+    }
+    // The fraction bits, by repeated squaring of `len / 2^whole` in [1, 2)
+    // with 63 fraction bits
+    let mut x = len << (63 - whole);
+    let mut cost = whole;
+    let mut bit = 0;
+    while bit < COST_SHIFT {
+        let square = (x as u128 * x as u128) >> 63;
+        cost <<= 1;
+        if square >> 64 != 0 {
+            cost |= 1;
+            x = (square >> 1) as u64;
+        } else {
+            x = square as u64;
+        }
+        bit += 1;
+    }
+    // Truncated, and never exact for a non-power of two
+    cost + 1
+}
+
+/// How `Filler` takes the digits of one base, computed once per `Alphabet`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Chunk {
+    /// The base
+    len: u64,
+    /// `log2(len)` in `2^-COST_SHIFT` bits, rounded up
+    cost: u32,
+    /// Digits taken at once, the most with `len^digits` below `2^32`
+    digits: u32,
+    /// `len^digits`
+    power: u64,
+    /// `2^64 / power`, rounded up
+    scale: u64,
+}
+
+impl Chunk {
+    pub(crate) const fn new(len: u64) -> Chunk {
+        let mut digits = 1;
+        let mut power = len;
+        while power * len < 1 << 32 {
+            power *= len;
+            digits += 1;
+        }
+        Chunk { len, cost: cost(len), digits, power, scale: (u64::MAX / power).wrapping_add(1) }
+    }
+
+    /// Precision a chunk needs
+    const fn need(&self) -> u32 {
+        let need = self.digits * self.cost;
+        if self.len.is_power_of_two() { need } else { need + MARGIN }
+    }
+}
+
+/// A `RandomSource` with a buffer of unused random bits.
+///
+/// The generated words are read as the binary expansion of a fraction,
+/// most significant bit first, and symbols are its digits in base
+/// `alphabet.len()`: a chunk of digits is the integer part of `fraction *
+/// len^digits`, and the rest of the product is the fraction left for the
+/// next chunk. A symbol costs `log2(len)` bits, e.g. 5.95 for an
+/// alphanumeric character; appending a word to the fraction drops less
+/// than a bit. The symbols do not depend on the byte order of the target.
+#[derive(Clone)]
+pub struct Filler<R: RandomSource> {
+    rng: R,
+    /// The last draw
+    words: Words<R>,
+    /// Index of the next unused word of `words`
+    word: usize,
+    /// The unused fraction, most significant bit first
+    fraction: u128,
+    /// Number of random bits left at the top of `fraction`, in
+    /// `2^-COST_SHIFT` bits
+    precision: u32,
+    /// The rest of the last chunk, as a 64-bit fraction of its digits
+    digits: u64,
+    /// Number of digits left in `digits`
+    pending: u32,
+    /// The base of `digits`
+    base: u64,
+}
+
+impl<R: RandomSource> Filler<R> {
+    /// Wrap `rng` with an empty buffer
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            words: Words::<R>::splat(0),
+            word: R::Width::LANES,
+            fraction: 0,
+            precision: 0,
+            digits: 0,
+            pending: 0,
+            base: 1,
+        }
+    }
+
+    /// The wrapped generator
+    pub fn rng(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    /// Unwrap the generator, dropping the buffered bits
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+
+    /// The next unused word
+    #[inline]
+    fn next_word(&mut self) -> u64 {
+        if self.word == R::Width::LANES {
+            self.words = self.rng.next();
+            self.word = 0;
+        }
+        let word = self.words.as_ref()[self.word];
+        self.word += 1;
+        word
+    }
+
+    /// Append the next word to the whole random bits of `fraction`,
+    /// replacing the bits below them
+    #[inline]
+    fn refill(&mut self) {
+        let kept = self.precision >> COST_SHIFT;
+        debug_assert!(kept <= 64);
+        let word = self.next_word() as u128;
+        self.fraction = self.fraction & !(u128::MAX >> kept) | word << (64 - kept);
+        self.precision = (kept + 64) << COST_SHIFT;
+    }
+
+    /// Take the next chunk of digits, as a 64-bit fraction
+    #[inline]
+    fn take_chunk(&mut self, chunk: &Chunk) {
+        if self.precision < chunk.need() {
+            self.refill();
+        }
+        let power = chunk.power as u128;
+        let low = (self.fraction as u64 as u128 * power) >> 64;
+        let value = ((self.fraction >> 64) * power + low) >> 64;
+        self.fraction = self.fraction.wrapping_mul(power);
+        self.precision -= chunk.digits * chunk.cost;
+        // Below `2^32` times `2^64 / power` rounded up, the error stays
+        // below every digit boundary
+        self.digits = value as u64 * chunk.scale;
+        self.pending = chunk.digits;
+        self.base = chunk.len;
+    }
+
+    /// Return the pending digits to `fraction`, before taking digits of
+    /// another base
+    #[cold]
+    fn put_back(&mut self) {
+        let power = (self.base as u128).pow(self.pending);
+        // fraction = (digits + fraction) / power, with `digits` as a number
+        let value = (self.digits as u128 * power) >> 64;
+        let high = value << 64 | self.fraction >> 64;
+        let low = (high % power) << 64 | self.fraction as u64 as u128;
+        self.fraction = ((high / power) << 64) | (low / power);
+        self.precision += self.pending * cost(self.base);
+        self.pending = 0;
+    }
+
+    /// Fill `out` with random symbols of `alphabet`
+    pub fn fill_from(&mut self, alphabet: &Alphabet, out: &mut [u8]) {
+        let chunk = alphabet.chunk();
+        if chunk.len == 1 {
+            out.fill(alphabet.symbol(0));
+            return;
+        }
+        if self.pending != 0 && self.base != chunk.len {
+            self.put_back();
+        }
+        let len = chunk.len as u128;
+        let pending = usize::min(self.pending as usize, out.len());
+        let (head, rest) = out.split_at_mut(pending);
+        for byte in head {
+            *byte = self.next_digit(alphabet, len);
+        }
+        // Whole chunks take every digit straight from the chunk's fraction
+        let mut chunks = rest.chunks_exact_mut(chunk.digits as usize);
+        for out in &mut chunks {
+            self.take_chunk(chunk);
+            let mut digits = self.digits;
+            for byte in out.iter_mut() {
+                let product = digits as u128 * len;
+                *byte = alphabet.symbol((product >> 64) as u8);
+                digits = product as u64;
+            }
+            self.pending = 0;
+        }
+        let tail = chunks.into_remainder();
+        if !tail.is_empty() {
+            self.take_chunk(chunk);
+            for byte in tail {
+                *byte = self.next_digit(alphabet, len);
+            }
+        }
+    }
+
+    /// The next pending digit of base `len`, as a symbol of `alphabet`
+    #[inline]
+    fn next_digit(&mut self, alphabet: &Alphabet, len: u128) -> u8 {
+        let product = self.digits as u128 * len;
+        self.digits = product as u64;
+        self.pending -= 1;
+        alphabet.symbol((product >> 64) as u8)
+    }
+
+    /// Fill `out` with random alphanumeric characters
+    pub fn fill_alphanum(&mut self, out: &mut [u8]) {
+        self.fill_from(&Alphabet::ALPHANUM, out);
+    }
+
+    /// Fill `out` with random ASCII characters
+    pub fn fill_ascii(&mut self, out: &mut [u8]) {
+        self.fill_from(&Alphabet::ASCII, out);
+    }
+
+    /// Fill `out` with base64 digits, 6 bits each
+    pub fn fill_b64(&mut self, out: &mut [u8]) {
+        self.fill_from(&Alphabet::B64, out);
+    }
+
+    /// Fill `out` with url-safe base64 digits, 6 bits each
+    pub fn fill_ub64(&mut self, out: &mut [u8]) {
+        self.fill_from(&Alphabet::UB64, out);
+    }
+}
@@ -0,0 +1,57 @@
+//! Supported lane counts, and the array types of their outputs.
+//!
+//! Output lengths are multiples of the lane count, which stable Rust cannot
+//! write as `[u8; 4 * LANES]` in generic code, so every supported count is
+//! mapped to its concrete array types through `Lanes`.
+
+use core::fmt::Debug;
+
+/// A fixed-size array of `T`
+pub trait LaneArray<T>: Copy + Debug + PartialEq + AsRef<[T]> + AsMut<[T]> {
+    /// Every element set to `value`
+    fn splat(value: T) -> Self;
+}
+
+impl<T: Copy + Debug + PartialEq, const N: usize> LaneArray<T> for [T; N] {
+    #[inline]
+    fn splat(value: T) -> Self {
+        [value; N]
+    }
+}
+
+/// The lane count `N` as a type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Width<const N: usize>;
+
+/// A supported lane count: 2, 4, 8 or 16 lanes of 64 bits
+pub trait Lanes {
+    /// Number of 64-bit lanes
+    const LANES: usize;
+    /// `[u64; LANES]`
+    type Words: LaneArray<u64>;
+    /// `[u16; 4 * LANES]`
+    type U16s: LaneArray<u16>;
+    /// `[u8; 4 * LANES]`, e.g. one character per 16-bit word
+    type Chars: LaneArray<u8>;
+    /// `[u8; 8 * LANES]`
+    type Bytes: LaneArray<u8>;
+    /// `Simd<u64, LANES>`
+    #[cfg(feature = "nightly")]
+    type Simd: From<Self::Words>;
+}
+
+macro_rules! lanes {
+    ($($lanes:literal)*) => {$(
+        impl Lanes for Width<$lanes> {
+            const LANES: usize = $lanes;
+            type Words = [u64; $lanes];
+            type U16s = [u16; 4 * $lanes];
+            type Chars = [u8; 4 * $lanes];
+            type Bytes = [u8; 8 * $lanes];
+            #[cfg(feature = "nightly")]
+            type Simd = core::simd::Simd<u64, $lanes>;
+        }
+    )*};
+}
+
+lanes!(2 4 8 16);
@@ -0,0 +1,63 @@
+//! A Weyl sequence through the mxm bit mixer from "The construct of a bit
+//! mixer", http://jonkagstrom.com/bit-mixer-construction/
+
+use super::{DEFAULT_LANES, Lanes, RandomSource, Width, splitmix64, splitmix_words};
+
+/// Multiplier of the mixer
+const MULC: u64 = 0x94d049bb133111eb;
+
+/// mxm over a Weyl sequence per lane
+///
+/// The shift in the mixer only changes the low byte of `x * MULC`, so an
+/// output is `MULC^2 * x + MULC * e` with `|e| < 256`: every lane is a Weyl
+/// sequence plus one of 511 offsets. `stats::birthday_spacings` finds the
+/// repeated spacings this leaves on hex and binary, where every tested
+/// value is half an output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mxm<const LANES: usize = DEFAULT_LANES> {
+    /// The counter of every lane
+    state: [u64; LANES],
+    /// The odd increment of every lane
+    increment: [u64; LANES],
+}
+
+impl<const LANES: usize> Mxm<LANES> {
+    /// Construct with the given lane counters and increments; the lowest
+    /// bit of the increments is set to make them odd.
+    ///
+    /// Lanes sharing an increment are shifted copies of one sequence, so
+    /// the increments should differ.
+    pub fn new(state: [u64; LANES], increment: [u64; LANES]) -> Self {
+        Self { state, increment: increment.map(|x| x | 1) }
+    }
+}
+
+/// The mxm mixer
+#[inline]
+fn mxm(mut x: u64) -> u64 {
+    x = x.wrapping_mul(MULC);
+    x ^= x >> 56;
+    x.wrapping_mul(MULC)
+}
+
+impl<const LANES: usize> RandomSource for Mxm<LANES>
+where
+    Width<LANES>: Lanes<Words = [u64; LANES]>,
+{
+    const NAME: &'static str = "mxm";
+    type Width = Width<LANES>;
+
+    /// Every lane of every stream gets its own increment, drawn from the
+    /// seed and `index`, so no two of them step through the same sequence
+    fn stream(seed: u64, index: u64) -> Self {
+        Self::new(splitmix_words(seed), splitmix_words(splitmix64(seed) ^ index))
+    }
+
+    #[inline]
+    fn next(&mut self) -> [u64; LANES] {
+        for (state, increment) in self.state.iter_mut().zip(self.increment) {
+            *state = state.wrapping_add(increment);
+        }
+        self.state.map(mxm)
+    }
+}
@@ -1,98 +1,605 @@
-#![feature(portable_simd)]
-use core::simd::*;
-use core::simd::cmp::SimdPartialOrd;
-use core::simd::num::SimdUint;
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
+//! Lanes are plain arrays operated on element-wise, which the compiler
+//! vectorizes on stable Rust; the `nightly` feature adds `core::simd`
+//! conversions.
+
+mod xoshiro;
+pub use xoshiro::Xoshiro256pp;
+mod aes_ctr;
+pub use aes_ctr::AesCtr;
+mod mxm;
+pub use mxm::Mxm;
+mod alphabet;
+pub use alphabet::Alphabet;
+mod bias;
+pub use bias::BiasReport;
+mod lanes;
+pub use lanes::{LaneArray, Lanes, Width};
+mod fill;
+pub use fill::Filler;
+pub mod stats;
+mod entropy;
+pub use entropy::{EntropySource, entropy_seed, fill_entropy};
+
+/// Default number of 64-bit lanes of the generators
+pub const DEFAULT_LANES: usize = 4;
+
+/// `[u64; LANES]` of a `RandomSource`
+pub type Words<R> = <<R as RandomSource>::Width as Lanes>::Words;
+/// `[u16; 4 * LANES]` of a `RandomSource`
+pub type U16s<R> = <<R as RandomSource>::Width as Lanes>::U16s;
+/// `[u8; 4 * LANES]` of a `RandomSource`
+pub type Chars<R> = <<R as RandomSource>::Width as Lanes>::Chars;
+/// `[u8; 8 * LANES]` of a `RandomSource`
+pub type Bytes<R> = <<R as RandomSource>::Width as Lanes>::Bytes;
+
+/// Weyl sequence increment of SplitMix64
+pub(crate) const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+/// The SplitMix64 output function
+#[inline]
+pub(crate) const fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// `N` unrelated words expanded from `seed` with SplitMix64
+pub(crate) fn splitmix_words<const N: usize>(seed: u64) -> [u64; N] {
+    let mut state = seed;
+    core::array::from_fn(|_| {
+        state = state.wrapping_add(GOLDEN_GAMMA);
+        splitmix64(state)
+    })
+}
+
+/// One xorshift64 step of a single lane
+#[inline]
+const fn xorshift(mut x: u64) -> u64 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 43;
+    x
+}
+
+/// A linear map over GF(2)^64, as the images of the 64 unit vectors
+type BitMatrix = [u64; 64];
+
+/// Apply a linear map to `x`
+const fn apply(matrix: &BitMatrix, x: u64) -> u64 {
+    let mut result = 0;
+    let mut bit = 0;
+    while bit < 64 {
+        if x >> bit & 1 != 0 {
+            result ^= matrix[bit];
+        }
+        bit += 1;
+    }
+    result
+}
+
+/// A linear map applied twice
+const fn square(matrix: &BitMatrix) -> BitMatrix {
+    let mut squared = [0; 64];
+    let mut bit = 0;
+    while bit < 64 {
+        squared[bit] = apply(matrix, matrix[bit]);
+        bit += 1;
+    }
+    squared
+}
+
+/// The map of `2^log2` xorshift64 steps, by repeated squaring
+const fn jump_matrix(log2: u32) -> BitMatrix {
+    let mut matrix = [0; 64];
+    let mut bit = 0;
+    while bit < 64 {
+        matrix[bit] = xorshift(1 << bit);
+        bit += 1;
+    }
+    let mut ii = 0;
+    while ii < log2 {
+        matrix = square(&matrix);
+        ii += 1;
+    }
+    matrix
+}
+
+/// log2 of the distance `SRng::jump` advances each lane
+pub const JUMP_LOG2: u32 = 48;
+
+/// Number of disjoint `SRng` streams of `2^JUMP_LOG2` outputs that fit in
+/// the `2^64 - 1` period
+pub const STREAMS: u64 = 1 << (64 - JUMP_LOG2);
+
+/// `2^JUMP_LOG2` xorshift64 steps
+const JUMP: BitMatrix = jump_matrix(JUMP_LOG2);
 
-const LANES: usize = 4;
 /// A basic random number generator based on xorshift64 with 64-bits of state
-pub struct SRng {
+///
+/// Each lane runs through all nonzero 64-bit values with period `2^64 - 1`.
+/// `jump` and `split` cut that cycle into disjoint streams of `2^JUMP_LOG2`
+/// outputs for parallel workers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SRng<const LANES: usize = DEFAULT_LANES> {
     /// The RNG's seed and state
-    seed: Simd<u64, LANES>,
+    seed: [u64; LANES],
 }
 
-impl SRng {
+impl<const LANES: usize> SRng<LANES> {
     /// Construct RNG with a given seed
-    pub fn new(seed: Simd<u64, LANES>) -> Self {
+    ///
+    /// A zero lane would stay zero forever, so zero lanes are replaced with
+    /// a fixed nonzero value.
+    pub fn new(mut seed: [u64; LANES]) -> Self {
+        for (lane, seed) in seed.iter_mut().enumerate() {
+            if *seed == 0 {
+                *seed = splitmix64(GOLDEN_GAMMA.wrapping_mul(lane as u64 + 1));
+            }
+        }
         Self {
             seed,
         }
     }
-    /// Generate a random number using mxm from "The construct of a bit mixer"
-    /// http://jonkagstrom.com/bit-mixer-construction/
+
+    /// Construct RNG from a single 64-bit seed, expanding it with SplitMix64
+    /// so that the lanes are unrelated
+    pub fn from_seed(seed: u64) -> Self {
+        Self::new(splitmix_words(seed))
+    }
+
+    /// Construct RNG with every lane seeded from operating system entropy,
+    /// see `fill_entropy`
+    pub fn from_entropy() -> Self {
+        let mut bytes = [0; 8];
+        Self::new(core::array::from_fn(|_| {
+            fill_entropy(&mut bytes);
+            u64::from_le_bytes(bytes)
+        }))
+    }
+
+    /// Advance every lane by `2^JUMP_LOG2` steps, as if `next` had been
+    /// called that many times
+    pub fn jump(&mut self) {
+        for seed in self.seed.iter_mut() {
+            *seed = apply(&JUMP, *seed);
+        }
+    }
+
+    /// Split off a generator for the next `2^JUMP_LOG2` outputs and jump
+    /// past them.
+    ///
+    /// Generators split from the same parent never overlap as long as each
+    /// one draws fewer than `2^JUMP_LOG2` outputs.
+    pub fn split(&mut self) -> Self {
+        let stream = self.clone();
+        self.jump();
+        stream
+    }
+
+    /// The generator of the `index`th stream from `seed`, the same as
+    /// calling `split` `index + 1` times on `SRng::from_seed(seed)`.
+    ///
+    /// Jumps by `2^(JUMP_LOG2 + k)` for every bit `k` set in `index`, so it
+    /// takes one matrix squaring per bit of `index`.
+    ///
+    /// Only indices below `STREAMS` give disjoint streams; larger ones wrap
+    /// around the period onto earlier streams.
+    pub fn stream(seed: u64, index: u64) -> Self {
+        let mut rng = Self::from_seed(seed);
+        let mut matrix = JUMP;
+        let mut index = index;
+        while index != 0 {
+            if index & 1 != 0 {
+                for seed in rng.seed.iter_mut() {
+                    *seed = apply(&matrix, *seed);
+                }
+            }
+            matrix = square(&matrix);
+            index >>= 1;
+        }
+        rng
+    }
+}
+
+/// A SIMD random generator producing `LANES` 64-bit words at a time, and
+/// the character helpers built on it
+pub trait RandomSource {
+    /// Short name, e.g. for benchmark output
+    const NAME: &'static str;
+
+    /// The lane count, `Width<LANES>`
+    type Width: Lanes;
+
+    /// The largest `index` of `stream` that does not overlap another stream
+    const MAX_STREAM: u64 = u64::MAX;
+
+    /// Construct the `index`th stream from `seed`. Streams of the same seed
+    /// are meant for parallel workers and should not overlap as long as
+    /// `index <= MAX_STREAM`.
+    fn stream(seed: u64, index: u64) -> Self where Self: Sized;
+
+    /// Generate the next `LANES` random words
+    fn next(&mut self) -> Words<Self>;
+
+    /// The next words as native-endian bytes
+    #[inline]
+    fn next_bytes(&mut self) -> Bytes<Self> {
+        let mut bytes = Bytes::<Self>::splat(0);
+        for (chunk, lane) in bytes.as_mut().chunks_exact_mut(8).zip(self.next().as_ref()) {
+            chunk.copy_from_slice(&lane.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// The next words as native-endian 16-bit words
     #[inline]
-    pub fn next(&mut self) -> Simd<u64, LANES> {
-        // let mulc = Simd::splat(0x94d049bb133111eb);
-        self.seed ^= self.seed << 13;
-        self.seed ^= self.seed >> 17;
-        self.seed ^= self.seed << 43;
-        // self.seed += mulc;
-        // self.seed *= mulc;
-        // self.seed ^= self.seed >> 56;
-        // self.seed *= mulc;
-        self.seed
+    fn next_u16s(&mut self) -> U16s<Self> {
+        let bytes = self.next_bytes();
+        let mut words = U16s::<Self>::splat(0);
+        for (word, pair) in words.as_mut().iter_mut().zip(bytes.as_ref().chunks_exact(2)) {
+            *word = u16::from_ne_bytes([pair[0], pair[1]]);
+        }
+        words
+    }
+
+    /// Generate the next words as a `core::simd` vector
+    #[cfg(feature = "nightly")]
+    #[inline]
+    fn next_simd(&mut self) -> <Self::Width as Lanes>::Simd {
+        self.next().into()
+    }
+
+    /// Generates 4*LANES random symbols of `alphabet`
+    #[inline]
+    fn random_from(&mut self, alphabet: &Alphabet) -> Chars<Self> {
+        let mut result = Chars::<Self>::splat(0);
+        for (symbol, x) in result.as_mut().iter_mut().zip(self.next_u16s().as_ref()) {
+            *symbol = alphabet.scale(*x);
+        }
+        result
+    }
+
+    /// Generates 4*LANES symbols of `alphabet`, exactly uniformly: 16-bit
+    /// values that would bias `random_from` are rejected and redrawn
+    #[inline]
+    fn random_from_uniform(&mut self, alphabet: &Alphabet) -> Chars<Self> {
+        let mut result = Chars::<Self>::splat(0);
+        let len = result.as_ref().len();
+        // Room for a whole draw past the last missing symbol, so accepted
+        // symbols are written without branching
+        let mut buffer = Bytes::<Self>::splat(0);
+        let buffer = buffer.as_mut();
+        let mut filled = 0;
+        while filled < len {
+            for &x in self.next_u16s().as_ref() {
+                let symbol = alphabet.scale_uniform(x);
+                buffer[filled] = symbol.unwrap_or(0);
+                filled += symbol.is_some() as usize;
+            }
+        }
+        result.as_mut().copy_from_slice(&buffer[..len]);
+        result
     }
 
     /// Generates 4*LANES random ASCII characters
-    pub fn random_ascii(&mut self) -> [u8; 4 * LANES] {
-        let max = Simd::<u32, {4*LANES}>::splat(0x7f - 0x20);
-        let space = Simd::splat(0x20);
-        let random1 = Simd::<u16, {4*LANES}>::from_ne_bytes(self.next().to_ne_bytes());
-        let result = random1.cast::<u32>() * max;
-        ((result >> 16).cast::<u8>() + space).to_array()
+    #[inline]
+    fn random_ascii(&mut self) -> Chars<Self> {
+        self.random_from(&Alphabet::ASCII)
     }
 
     /// Generates 4*LANES random alphanumeric characters
-    pub fn random_alphanum(&mut self) -> [u8; 4 * LANES] {
-        let max = Simd::<u32, {4*LANES}>::splat(10 + 26 + 26);
-        let random1 = Simd::<u16, {4*LANES}>::from_ne_bytes(self.next().to_ne_bytes());
-        let result = random1.cast::<u32>() * max;
-        let mut result = (result >> 16).cast::<u8>() + Simd::splat(b'0');
-        let skip_ranges = [
-            (b'9', b'A' - b'9' - 1),
-            (b'Z', b'a' - b'Z' - 1),
-        ];
-        for (start, shift) in skip_ranges {
-            let mask = result.simd_gt(Simd::splat(start));
-            let shifted = result + Simd::splat(shift);
-            result = mask.select(shifted, result);
-        }
-        result.to_array()
+    #[inline]
+    fn random_alphanum(&mut self) -> Chars<Self> {
+        self.random_from(&Alphabet::ALPHANUM)
+    }
+
+    /// Generates 4*LANES exactly uniform ASCII characters
+    #[inline]
+    fn random_ascii_uniform(&mut self) -> Chars<Self> {
+        self.random_from_uniform(&Alphabet::ASCII)
+    }
+
+    /// Generates 4*LANES exactly uniform alphanumeric characters
+    #[inline]
+    fn random_alphanum_uniform(&mut self) -> Chars<Self> {
+        self.random_from_uniform(&Alphabet::ALPHANUM)
     }
 
     /// Generates 8*LANES url-safe base64 digits
-    pub fn random_ub64(&mut self) -> [u8; 8 * LANES] {
-        let mask = Simd::splat(0x3f);
-        let mut result = self.next().to_ne_bytes() & mask;
-        result += Simd::splat(b'+');
-        let skip_ranges = [
-            (b'-', b'0' - b'-' - 1),
-            (b'9', b'A' - b'9' - 1),
-            (b'Z', b'_' - b'Z' - 1),
-            (b'_', b'a' - b'_' - 1),
-        ];
-        for (start, shift) in skip_ranges {
-            let mask = result.simd_gt(Simd::splat(start));
-            let shifted = result + Simd::splat(shift);
-            result = mask.select(shifted, result);
-        }
-        result.to_array()
+    #[inline]
+    fn random_ub64(&mut self) -> Bytes<Self> {
+        let mut result = self.next_bytes();
+        for byte in result.as_mut() {
+            *byte = Alphabet::UB64.symbol(*byte & 0x3f);
+        }
+        result
     }
 
     /// Generates 8*LANES base64 digits
-    pub fn random_b64(&mut self) -> [u8; 8 * LANES] {
-        let mask = Simd::splat(0x3f);
-        let mut result = self.next().to_ne_bytes() & mask;
-        result += Simd::splat(b'+');
-        let skip_ranges = [
-            (b'+', b'/' - b'+' - 1),
-            (b'9', b'A' - b'9' - 1),
-            (b'Z', b'a' - b'Z' - 1),
-        ];
-        for (start, shift) in skip_ranges {
-            let mask = result.simd_gt(Simd::splat(start));
-            let shifted = result + Simd::splat(shift);
-            result = mask.select(shifted, result);
-        }
-        result.to_array()
+    #[inline]
+    fn random_b64(&mut self) -> Bytes<Self> {
+        let mut result = self.next_bytes();
+        for byte in result.as_mut() {
+            *byte = Alphabet::B64.symbol(*byte & 0x3f);
+        }
+        result
+    }
+}
+
+impl<const LANES: usize> RandomSource for SRng<LANES>
+where
+    Width<LANES>: Lanes<Words = [u64; LANES]>,
+{
+    const NAME: &'static str = "xorshift";
+    const MAX_STREAM: u64 = STREAMS - 1;
+    type Width = Width<LANES>;
+
+    fn stream(seed: u64, index: u64) -> Self {
+        SRng::stream(seed, index)
+    }
+
+    /// One xorshift64 step of every lane
+    #[inline]
+    fn next(&mut self) -> [u64; LANES] {
+        for seed in self.seed.iter_mut() {
+            *seed = xorshift(*seed);
+        }
+        self.seed
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod nightly {
+    use core::simd::Simd;
+    use super::SRng;
+
+    impl<const LANES: usize> SRng<LANES> {
+        /// Construct RNG with a given `core::simd` seed
+        pub fn from_simd(seed: Simd<u64, LANES>) -> Self {
+            Self::new(seed.to_array())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_matches_steps() {
+        let matrix = jump_matrix(10);
+        let mut x = 0x0123456789abcdef;
+        let jumped = apply(&matrix, x);
+        for _ in 0..1 << 10 {
+            x = xorshift(x);
+        }
+        assert_eq!(jumped, x);
+    }
+
+    #[test]
+    fn split_streams() {
+        let mut parent: SRng = SRng::from_seed(1);
+        let first = parent.split();
+        let second = parent.split();
+        assert_eq!(first, <SRng>::stream(1, 0));
+        assert_eq!(second, <SRng>::stream(1, 1));
+        assert_ne!(first.seed, second.seed);
+    }
+
+    #[test]
+    fn stream_matches_jumps() {
+        let mut rng: SRng = SRng::from_seed(1);
+        for index in 0..40 {
+            assert_eq!(rng, SRng::stream(1, index));
+            rng.jump();
+        }
+        // Far streams only take a squaring per bit of the index
+        let far = <SRng>::stream(1, 1 << 40);
+        assert_ne!(far, <SRng>::stream(1, 0));
+        assert_ne!(far, <SRng>::stream(1, 1));
+        // `STREAMS` jumps are one step more than the period
+        let mut first = <SRng>::stream(1, 0);
+        first.next();
+        assert_eq!(<SRng>::stream(1, STREAMS), first);
+    }
+
+    fn check_streams<R: RandomSource>() {
+        let mut first = R::stream(1, 0);
+        let mut second = R::stream(1, 1);
+        let mut again = R::stream(1, 0);
+        for _ in 0..100 {
+            let words = first.next();
+            assert_eq!(words, again.next());
+            assert_ne!(words, second.next());
+        }
+    }
+
+    #[test]
+    fn sources_streams() {
+        check_streams::<SRng>();
+        check_streams::<Xoshiro256pp>();
+        check_streams::<AesCtr>();
+        check_streams::<Mxm>();
+        check_streams::<SRng<2>>();
+        check_streams::<Xoshiro256pp<8>>();
+        check_streams::<AesCtr<16>>();
+        check_streams::<Mxm<16>>();
+    }
+
+    #[test]
+    fn xoshiro_stream_matches_jumps() {
+        let mut rng: Xoshiro256pp = Xoshiro256pp::from_seed(1);
+        for index in 0..20 {
+            assert_eq!(rng, Xoshiro256pp::stream(1, index));
+            rng.jump();
+        }
+        // Far streams only take a squaring per bit of the index
+        let far = <Xoshiro256pp>::stream(1, u64::MAX);
+        assert_ne!(far, <Xoshiro256pp>::stream(1, 0));
+        assert_ne!(far, <Xoshiro256pp>::stream(1, 1));
+    }
+
+    #[test]
+    fn mxm_far_streams() {
+        // Indices from 2^16 used to wrap around onto earlier streams
+        let first = <Mxm>::stream(1, 0);
+        for index in [1 << 16, 1 << 32, u64::MAX] {
+            assert_ne!(<Mxm>::stream(1, index), first);
+        }
+    }
+
+    #[test]
+    fn lane_widths() {
+        let mut narrow = SRng::<2>::from_seed(1);
+        let mut wide = SRng::<16>::from_seed(1);
+        assert_eq!(narrow.random_alphanum().len(), 8);
+        assert_eq!(wide.random_alphanum().len(), 64);
+        assert_eq!(wide.random_b64().len(), 128);
+        assert_eq!(wide.random_from_uniform(&Alphabet::HEX).len(), 64);
+
+        // Lanes are independent, so a narrow generator is a prefix of a
+        // wide one with the same lane seeds
+        let seed: [u64; 16] = splitmix_words(2);
+        let mut narrow = SRng::<2>::new([seed[0], seed[1]]);
+        let mut wide: SRng<16> = SRng::new(seed);
+        for _ in 0..100 {
+            assert_eq!(narrow.next(), wide.next()[..2]);
+        }
+    }
+
+    #[test]
+    fn alphabets() {
+        assert_eq!(Alphabet::ASCII.len(), 95);
+        assert_eq!(Alphabet::ALPHANUM.len(), 62);
+        assert_eq!(Alphabet::B64.len(), 64);
+        assert_eq!(Alphabet::UB64.len(), 64);
+        assert_eq!(Alphabet::LOWERCASE.len(), 26);
+
+        let mut rng: SRng = SRng::from_seed(1);
+        for alphabet in [Alphabet::HEX, Alphabet::SHELL_SAFE, Alphabet::new(b"xyz")] {
+            for _ in 0..100 {
+                assert!(rng.random_from(&alphabet).iter().all(|&byte| alphabet.contains(byte)));
+            }
+        }
+        for _ in 0..100 {
+            assert!(rng.random_ub64().iter().all(|&byte| Alphabet::UB64.contains(byte)));
+        }
+    }
+
+    #[test]
+    fn uniform_sampling() {
+        for alphabet in [Alphabet::ALPHANUM, Alphabet::ASCII, Alphabet::new(b"xyz")] {
+            assert!(BiasReport::multiply_shift(&alphabet).max_bias() > 0.0);
+            let mut exact = BiasReport::new(&alphabet);
+            for x in 0..=u16::MAX {
+                exact.add(alphabet.scale_uniform(x).as_slice());
+            }
+            assert_eq!(exact.max_bias(), 0.0);
+        }
+        assert_eq!(BiasReport::multiply_shift(&Alphabet::HEX).max_bias(), 0.0);
+
+        let mut rng: SRng = SRng::from_seed(1);
+        let report = BiasReport::measure(&Alphabet::ALPHANUM, 10000, || rng.random_alphanum_uniform());
+        assert_eq!(report.samples(), 10000 * 4 * DEFAULT_LANES as u64);
+        assert!(report.max_bias() < 0.05);
+    }
+
+    #[test]
+    fn fills_keep_leftover_bits() {
+        for alphabet in [Alphabet::ALPHANUM, Alphabet::B64, Alphabet::new(b"xyz")] {
+            let mut whole = Filler::new(<SRng>::from_seed(1));
+            let mut expected = [0; 1000];
+            whole.fill_from(&alphabet, &mut expected);
+
+            // Pieces of any length continue where the last one stopped
+            let mut pieces = Filler::new(<SRng>::from_seed(1));
+            let mut out = [0; 1000];
+            let mut rest = &mut out[..];
+            for len in (1..).cycle() {
+                let len = usize::min(len % 37, rest.len());
+                let (head, tail) = rest.split_at_mut(len);
+                pieces.fill_from(&alphabet, head);
+                rest = tail;
+                if rest.is_empty() {
+                    break;
+                }
+            }
+            assert_eq!(out, expected);
+            assert!(out.iter().all(|&byte| alphabet.contains(byte)));
+        }
+
+        // Base64 digits take 6 bits: 64 digits use 6 words
+        let mut filler = Filler::new(<SRng>::from_seed(3));
+        filler.fill_b64(&mut [0; 64]);
+        let mut rng: SRng = SRng::from_seed(3);
+        for _ in 0..2 {
+            rng.next();
+        }
+        assert_eq!(filler.into_inner(), rng);
+    }
+
+    /// Bits drawn from `SRng::from_seed(seed)` by `filler`
+    fn bits_drawn(seed: u64, filler: Filler<SRng>) -> f64 {
+        let used = filler.into_inner();
+        let mut rng: SRng = SRng::from_seed(seed);
+        let mut draws = 0;
+        while rng != used {
+            rng.next();
+            draws += 1;
+        }
+        (draws * 64 * DEFAULT_LANES) as f64
+    }
+
+    #[test]
+    fn fills_draw_log2_len_bits() {
+        // Appending a word drops less than a bit, and the last draw is
+        // partly unused
+        let slack = 64.0 + 64.0 * DEFAULT_LANES as f64;
+        let mut filler = Filler::new(<SRng>::from_seed(4));
+        filler.fill_alphanum(&mut vec![0; 100_000]);
+        let expected = 100_000.0 * 62f64.log2();
+        let drawn = bits_drawn(4, filler);
+        assert!(drawn >= expected && drawn <= expected * 65.0 / 64.0 + slack, "{drawn} bits");
+
+        // Switching alphabets keeps the digits of the other base
+        let mut filler = Filler::new(<SRng>::from_seed(5));
+        let mut expected = 0.0;
+        for len in 1..400 {
+            let alphabet = if len % 2 == 0 { Alphabet::ALPHANUM } else { Alphabet::HEX };
+            filler.fill_from(&alphabet, &mut vec![0; len % 13]);
+            expected += (len % 13) as f64 * (alphabet.len() as f64).log2();
+        }
+        let drawn = bits_drawn(5, filler);
+        assert!(drawn >= expected && drawn <= expected * 65.0 / 64.0 + slack, "{drawn} bits");
+    }
+
+    #[test]
+    fn fills_read_words_high_bits_first() {
+        let mut filler = Filler::new(<SRng>::from_seed(6));
+        let mut out = [0; 16 * DEFAULT_LANES];
+        filler.fill_from(&Alphabet::HEX, &mut out);
+        let mut rng: SRng = SRng::from_seed(6);
+        let expected: String = rng.next().iter().map(|word| format!("{word:016x}")).collect();
+        assert_eq!(out, expected.as_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn alphabet_duplicates() {
+        Alphabet::new(b"abca");
+    }
+
+    #[test]
+    fn entropy_seeding() {
+        let first: SRng = SRng::from_entropy();
+        let second: SRng = SRng::from_entropy();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn zero_lanes_fixed_up() {
+        let mut rng: SRng = SRng::new([0; DEFAULT_LANES]);
+        assert!(rng.next().iter().all(|&lane| lane != 0));
     }
 }
@@ -0,0 +1,405 @@
+//! Statistical tests of generator output, to check that generated
+//! candidates cover the search space uniformly.
+//!
+//! Each test computes a statistic with a known distribution for an ideal
+//! generator and reports its p-value; a test fails when the p-value is
+//! below `ALPHA`.
+
+use core::fmt;
+use super::{Alphabet, Filler, Lanes, RandomSource};
+
+/// p-value below which a test fails
+pub const ALPHA: f64 = 0.001;
+
+/// Result of one statistical test
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatTest {
+    /// Test name
+    pub name: &'static str,
+    /// The test statistic
+    pub statistic: f64,
+    /// Probability of a statistic at least this extreme from an ideal
+    /// generator
+    pub p_value: f64,
+}
+
+impl StatTest {
+    /// Whether the p-value is at least `ALPHA`
+    pub fn passed(&self) -> bool {
+        self.p_value >= ALPHA
+    }
+}
+
+/// Results of `run_tests`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsReport {
+    /// Name of the tested generator
+    pub rng: &'static str,
+    /// Number of lanes of the tested generator
+    pub lanes: usize,
+    /// Number of symbols in the tested alphabet
+    pub symbols: usize,
+    /// The test results
+    pub tests: Vec<StatTest>,
+}
+
+impl StatsReport {
+    /// Whether every test passed
+    pub fn passed(&self) -> bool {
+        self.tests.iter().all(StatTest::passed)
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} x{}, {} symbols:", self.rng, self.lanes, self.symbols)?;
+        for test in &self.tests {
+            writeln!(f, "  {:<24} {:>14.4} p={:<10.6} {}", test.name, test.statistic,
+                test.p_value, if test.passed() { "pass" } else { "FAIL" })?;
+        }
+        write!(f, "{}", if self.passed() { "PASS" } else { "FAIL" })
+    }
+}
+
+/// Run every test on about `samples` symbols of `alphabet` from `rng`, drawn
+/// with `Filler::fill_from` like search candidates
+pub fn run_tests<R: RandomSource>(rng: R, alphabet: &Alphabet, samples: usize) -> StatsReport {
+    let mut filler = Filler::new(rng);
+    let mut symbols = vec![0; samples];
+    filler.fill_from(alphabet, &mut symbols);
+    let indices = indices(alphabet, &symbols);
+
+    let lanes = R::Width::LANES;
+    let mut words = Vec::with_capacity(samples.div_ceil(lanes) * lanes);
+    while words.len() < samples {
+        words.extend_from_slice(filler.rng().next().as_ref());
+    }
+
+    StatsReport {
+        rng: R::NAME,
+        lanes,
+        symbols: alphabet.len(),
+        tests: vec![
+            chi_square(&indices, alphabet.len()),
+            serial_correlation(&indices),
+            runs(&indices, alphabet.len()),
+            lane_correlation(&words, lanes),
+            birthday_spacings(&mut filler, alphabet, samples),
+        ],
+    }
+}
+
+/// Fewest symbols for which every test of `run_tests` is meaningful: five
+/// expected hits per symbol for the chi-square and runs tests, 32 draws for
+/// the lane correlation, and one full set of birthdays. Fewer give
+/// undefined or spurious results.
+pub fn min_samples(alphabet: &Alphabet, lanes: usize) -> usize {
+    let (length, birthdays) = birthday_size(alphabet);
+    (5 * alphabet.len()).max(32 * lanes).max(birthdays * length)
+}
+
+/// Positions of `symbols` in `alphabet`
+fn indices(alphabet: &Alphabet, symbols: &[u8]) -> Vec<u32> {
+    let mut index = [0; 256];
+    for (ii, &symbol) in alphabet.symbols().iter().enumerate() {
+        index[symbol as usize] = ii as u32;
+    }
+    symbols.iter().map(|&symbol| index[symbol as usize]).collect()
+}
+
+/// Pearson's chi-square test of the symbol frequencies against uniform
+pub fn chi_square(indices: &[u32], symbols: usize) -> StatTest {
+    let mut counts = vec![0_u64; symbols];
+    for &index in indices {
+        counts[index as usize] += 1;
+    }
+    let expected = indices.len() as f64 / symbols as f64;
+    let statistic = counts.iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum();
+    StatTest {
+        name: "chi-square",
+        statistic,
+        p_value: chi_square_p(statistic, (symbols - 1) as f64),
+    }
+}
+
+/// Knuth's serial correlation coefficient of consecutive symbols
+pub fn serial_correlation(indices: &[u32]) -> StatTest {
+    let n = indices.len() as f64;
+    let (mut sum, mut squares, mut products) = (0.0, 0.0, 0.0);
+    for (ii, &x) in indices.iter().enumerate() {
+        let x = x as f64;
+        let next = indices[(ii + 1) % indices.len()] as f64;
+        sum += x;
+        squares += x * x;
+        products += x * next;
+    }
+    let coefficient = (n * products - sum * sum) / (n * squares - sum * sum);
+    let mean = -1.0 / (n - 1.0);
+    let deviation = n / ((n - 1.0) * (n - 2.0).sqrt());
+    StatTest {
+        name: "serial correlation",
+        statistic: coefficient,
+        p_value: normal_p((coefficient - mean) / deviation),
+    }
+}
+
+/// Wald-Wolfowitz runs test of symbols in the lower and upper half of the
+/// alphabet
+pub fn runs(indices: &[u32], symbols: usize) -> StatTest {
+    let low = |index: u32| 2 * (index as usize) < symbols;
+    let n1 = indices.iter().filter(|&&index| low(index)).count() as f64;
+    let n2 = indices.len() as f64 - n1;
+    let n = n1 + n2;
+    let runs = 1 + indices.windows(2).filter(|pair| low(pair[0]) != low(pair[1])).count();
+    let mean = 2.0 * n1 * n2 / n + 1.0;
+    let variance = (mean - 1.0) * (mean - 2.0) / (n - 1.0);
+    StatTest {
+        name: "runs",
+        statistic: runs as f64,
+        p_value: normal_p((runs as f64 - mean) / variance.sqrt()),
+    }
+}
+
+/// Correlation between every pair of lanes of the raw words, `lanes` words
+/// per draw. The squared z-scores of all pairs are summed into a
+/// chi-square statistic.
+pub fn lane_correlation(words: &[u64], lanes: usize) -> StatTest {
+    // The top 53 bits as uniform values in [-0.5, 0.5)
+    let uniform = |word: u64| (word >> 11) as f64 * (1.0 / (1_u64 << 53) as f64) - 0.5;
+    let draws = words.chunks_exact(lanes);
+    let n = draws.len() as f64;
+    let mut statistic = 0.0;
+    for ii in 0..lanes {
+        for jj in ii + 1..lanes {
+            let (mut product, mut square_i, mut square_j) = (0.0, 0.0, 0.0);
+            for draw in draws.clone() {
+                let (x, y) = (uniform(draw[ii]), uniform(draw[jj]));
+                product += x * y;
+                square_i += x * x;
+                square_j += y * y;
+            }
+            let correlation = product / (square_i * square_j).sqrt();
+            statistic += correlation * correlation * n;
+        }
+    }
+    let pairs = lanes * (lanes - 1) / 2;
+    StatTest {
+        name: "lane cross-correlation",
+        statistic,
+        p_value: chi_square_p(statistic, pairs as f64),
+    }
+}
+
+/// Marsaglia's birthday spacings test on strings of about 32 bits of
+/// symbols: the number of repeated spacings between sorted birthdays is
+/// Poisson distributed
+pub fn birthday_spacings<R: RandomSource>(
+    filler: &mut Filler<R>,
+    alphabet: &Alphabet,
+    samples: usize,
+) -> StatTest {
+    let symbols = alphabet.len();
+    let (length, birthdays) = birthday_size(alphabet);
+    let days = (symbols as f64).powi(length as i32);
+    let lambda = (birthdays as f64).powi(3) / (4.0 * days);
+    let repeats = (samples / (birthdays * length)).max(1);
+
+    let mut digits = vec![0; birthdays * length];
+    let mut repeated = 0;
+    for _ in 0..repeats {
+        filler.fill_from(alphabet, &mut digits);
+        let mut values: Vec<u64> = indices(alphabet, &digits)
+            .chunks_exact(length)
+            .map(|digits| digits.iter().fold(0, |value, &digit| value * symbols as u64 + digit as u64))
+            .collect();
+        values.sort_unstable();
+        let mut spacings: Vec<u64> = values.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        spacings.sort_unstable();
+        repeated += spacings.windows(2).filter(|pair| pair[0] == pair[1]).count() as u64;
+    }
+
+    let mean = lambda * repeats as f64;
+    let below = poisson_cdf(repeated, mean);
+    let above = if repeated == 0 { 1.0 } else { 1.0 - poisson_cdf(repeated - 1, mean) };
+    StatTest {
+        name: "birthday spacings",
+        statistic: repeated as f64,
+        p_value: (2.0 * below.min(above)).min(1.0),
+    }
+}
+
+/// Digits per birthday, up to 32 bits, and birthdays per repeat of
+/// `birthday_spacings`
+fn birthday_size(alphabet: &Alphabet) -> (usize, usize) {
+    let symbols = alphabet.len() as f64;
+    let length = (32.0 / symbols.log2()).floor().max(1.0) as usize;
+    let days = symbols.powi(length as i32);
+    (length, (8.0 * days).cbrt().round().max(2.0) as usize)
+}
+
+/// `ln(Gamma(x))` for `x > 0`, Lanczos approximation with g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8,
+        771.323_428_777_653_1, -176.615_029_162_140_6, 12.507_343_278_686_905,
+        -0.138_571_095_265_720_12, 9.984_369_578_019_572e-6, 1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (ii, &coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + ii as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * core::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Regularized upper incomplete gamma function `Q(a, x)`, by the series
+/// for `x < a + 1` and the continued fraction otherwise
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let scale = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        return 1.0 - sum * scale;
+    }
+    // Modified Lentz's method
+    let tiny = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for n in 1..1000 {
+        let an = -(n as f64) * (n as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    scale * h
+}
+
+/// Upper tail of the chi-square distribution with `df` degrees of freedom
+fn chi_square_p(statistic: f64, df: f64) -> f64 {
+    gamma_q(df / 2.0, statistic / 2.0)
+}
+
+/// Two-sided p-value of a standard normal z-score
+fn normal_p(z: f64) -> f64 {
+    gamma_q(0.5, z * z / 2.0)
+}
+
+/// `P(X <= k)` for `X` Poisson distributed with mean `mean`
+fn poisson_cdf(k: u64, mean: f64) -> f64 {
+    gamma_q(k as f64 + 1.0, mean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AesCtr, Mxm, SRng, Words, Width, Xoshiro256pp};
+
+    #[test]
+    fn distributions() {
+        assert!((normal_p(1.959_963_985) - 0.05).abs() < 1e-9);
+        assert!((chi_square_p(3.841_458_821, 1.0) - 0.05).abs() < 1e-9);
+        assert!((chi_square_p(18.307_038_05, 10.0) - 0.05).abs() < 1e-9);
+        assert!((poisson_cdf(2, 1.0) - 2.5 * (-1.0_f64).exp()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn generators_pass() {
+        for alphabet in [Alphabet::ALPHANUM, Alphabet::B64, Alphabet::new(b"01")] {
+            let mut reports = vec![
+                run_tests(<SRng>::from_seed(1), &alphabet, 100_000),
+                run_tests(SRng::<16>::from_seed(1), &alphabet, 100_000),
+                run_tests(<Xoshiro256pp>::from_seed(1), &alphabet, 100_000),
+                run_tests(<AesCtr>::stream(1, 0), &alphabet, 100_000),
+            ];
+            // See `mxm_half_words_fail`
+            if alphabet.len() != 2 {
+                reports.push(run_tests(<Mxm>::stream(1, 0), &alphabet, 100_000));
+            }
+            for report in reports {
+                assert!(report.passed(), "{report}");
+            }
+        }
+    }
+
+    /// Every lane counts up, which the tests should reject
+    struct Counter([u64; 4]);
+
+    impl RandomSource for Counter {
+        const NAME: &'static str = "counter";
+        type Width = Width<4>;
+
+        fn stream(seed: u64, _index: u64) -> Self {
+            Counter([seed; 4])
+        }
+
+        fn next(&mut self) -> Words<Self> {
+            for word in self.0.iter_mut() {
+                *word = word.wrapping_add(1);
+            }
+            self.0
+        }
+    }
+
+    /// mxm lanes are Weyl sequences plus a few offsets, which the birthday
+    /// spacings catch on hex and binary
+    #[test]
+    fn mxm_half_words_fail() {
+        for alphabet in [Alphabet::HEX, Alphabet::new(b"01")] {
+            let report = run_tests(<Mxm>::stream(1, 0), &alphabet, 100_000);
+            assert!(!report.tests[4].passed(), "{report}");
+        }
+    }
+
+    /// mxm lanes stepping by the same increment are shifted copies of one
+    /// sequence, which the lane cross-correlation catches
+    #[test]
+    fn mxm_shared_increment_fails() {
+        let mut rng = Mxm::<16>::new(crate::splitmix_words(1), [0x94d049bb133111eb; 16]);
+        let words: Vec<u64> = (0..1 << 16).flat_map(|_| rng.next()).collect();
+        assert!(!lane_correlation(&words, 16).passed());
+        let mut rng = Mxm::<16>::stream(1, 0);
+        let words: Vec<u64> = (0..1 << 16).flat_map(|_| rng.next()).collect();
+        assert!(lane_correlation(&words, 16).passed());
+    }
+
+    #[test]
+    fn min_samples_defined() {
+        for alphabet in [Alphabet::ALPHANUM, Alphabet::HEX, Alphabet::new(b"01")] {
+            let report = run_tests(SRng::<16>::from_seed(1), &alphabet, min_samples(&alphabet, 16));
+            assert!(report.tests.iter().all(|test| test.p_value.is_finite()), "{report}");
+        }
+    }
+
+    #[test]
+    fn counter_fails() {
+        let report = run_tests(Counter::stream(1, 0), &Alphabet::ALPHANUM, 100_000);
+        assert!(!report.passed(), "{report}");
+    }
+}
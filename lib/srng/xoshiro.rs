@@ -0,0 +1,136 @@
+//! xoshiro256++ by Blackman and Vigna, https://prng.di.unimi.it/
+//!
+//! Every lane is an independent generator; the state is stored as four
+//! words of `LANES` lanes so the step vectorizes.
+
+use super::{DEFAULT_LANES, GOLDEN_GAMMA, Lanes, RandomSource, Width, splitmix_words};
+
+/// `jump` polynomial, advancing by 2^128 steps
+const JUMP: [u64; 4] = [0x180ec6d33cfd0aba, 0xd5a61266f0c9392c, 0xa9582618e03fc9aa, 0x39abdc4529b1661c];
+
+/// A linear map over GF(2)^256, as the images of the 256 unit states
+type JumpMatrix = [[u64; 4]; 256];
+
+/// Apply a linear map to the state of one lane
+fn apply(matrix: &JumpMatrix, state: [u64; 4]) -> [u64; 4] {
+    let mut result = [0; 4];
+    for (bit, image) in matrix.iter().enumerate() {
+        if state[bit / 64] >> (bit % 64) & 1 != 0 {
+            for (result, image) in result.iter_mut().zip(image) {
+                *result ^= image;
+            }
+        }
+    }
+    result
+}
+
+/// A linear map applied twice
+fn square(matrix: &JumpMatrix) -> JumpMatrix {
+    core::array::from_fn(|bit| apply(matrix, matrix[bit]))
+}
+
+/// The map of `jump`, from jumping every unit state
+fn jump_matrix() -> JumpMatrix {
+    core::array::from_fn(|bit| {
+        let mut unit = [0; 4];
+        unit[bit / 64] = 1 << (bit % 64);
+        let mut rng = Xoshiro256pp::<1> { state: unit.map(|word| [word]) };
+        rng.jump();
+        rng.state.map(|[word]| word)
+    })
+}
+
+/// xoshiro256++ with 256 bits of state per lane
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Xoshiro256pp<const LANES: usize = DEFAULT_LANES> {
+    /// State word `i` of every lane
+    state: [[u64; LANES]; 4],
+}
+
+impl<const LANES: usize> Xoshiro256pp<LANES> {
+    /// Construct from a single 64-bit seed, expanded with SplitMix64.
+    ///
+    /// SplitMix64 never repeats a word within 2^64 outputs, so no lane can
+    /// get the all-zero state.
+    pub fn from_seed(seed: u64) -> Self {
+        // Lane `l` continues the SplitMix64 sequence after lane `l - 1`
+        let lanes: [[u64; 4]; LANES] = core::array::from_fn(|lane| {
+            splitmix_words(seed.wrapping_add(GOLDEN_GAMMA.wrapping_mul(4 * lane as u64)))
+        });
+        Self { state: core::array::from_fn(|ii| lanes.map(|lane| lane[ii])) }
+    }
+
+    /// One step of every lane
+    #[inline]
+    fn step(&mut self) -> [u64; LANES] {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let mut result = [0; LANES];
+        for lane in 0..LANES {
+            result[lane] = s0[lane].wrapping_add(s3[lane]).rotate_left(23).wrapping_add(s0[lane]);
+            let t = s1[lane] << 17;
+            s2[lane] ^= s0[lane];
+            s3[lane] ^= s1[lane];
+            s1[lane] ^= s2[lane];
+            s0[lane] ^= s3[lane];
+            s2[lane] ^= t;
+            s3[lane] = s3[lane].rotate_left(45);
+        }
+        result
+    }
+
+    /// Advance every lane by 2^128 steps
+    pub fn jump(&mut self) {
+        let mut jumped = [[0; LANES]; 4];
+        for word in JUMP {
+            for bit in 0..64 {
+                if word >> bit & 1 != 0 {
+                    for (jumped, state) in jumped.iter_mut().zip(&self.state) {
+                        for (jumped, state) in jumped.iter_mut().zip(state) {
+                            *jumped ^= state;
+                        }
+                    }
+                }
+                self.step();
+            }
+        }
+        self.state = jumped;
+    }
+}
+
+impl<const LANES: usize> RandomSource for Xoshiro256pp<LANES>
+where
+    Width<LANES>: Lanes<Words = [u64; LANES]>,
+{
+    const NAME: &'static str = "xoshiro256++";
+    type Width = Width<LANES>;
+
+    /// Stream `index` is `index` jumps of 2^128 steps from the seed.
+    ///
+    /// Jumps by `2^(128 + k)` for every bit `k` set in `index`, so it takes
+    /// one matrix squaring per bit of `index`.
+    fn stream(seed: u64, index: u64) -> Self {
+        let mut rng = Self::from_seed(seed);
+        let mut matrix = jump_matrix();
+        let mut index = index;
+        while index != 0 {
+            if index & 1 != 0 {
+                for lane in 0..LANES {
+                    let state = apply(&matrix, rng.state.map(|word| word[lane]));
+                    for (word, state) in rng.state.iter_mut().zip(state) {
+                        word[lane] = state;
+                    }
+                }
+            }
+            index >>= 1;
+            if index != 0 {
+                matrix = square(&matrix);
+            }
+        }
+        rng
+    }
+
+    #[inline]
+    fn next(&mut self) -> [u64; LANES] {
+        self.step()
+    }
+}
@@ -1,158 +1,33 @@
-#![feature(portable_simd)]
-#![allow(clippy::needless_return)]
-
-use core::simd::{
-    cmp::SimdPartialOrd,
-    Simd, ToBytes,
+use core::sync::atomic::{AtomicBool, Ordering};
+use srng::{Alphabet, AesCtr, Filler, Lanes, Mxm, RandomSource, SRng, Width, Xoshiro256pp};
+use srng::stats::{StatsReport, min_samples, run_tests};
+use simd_aes::Block;
+use refterm_hash::{
+    ComputeGlyphHash,
+    GlyphHashParams,
+    chosen_prefix,
+    find_ascii_zeros,
+    invert,
+    padding_collision,
+    prefix_collision,
+    preimage,
 };
-use core::convert::TryInto;
-use srng::SRng;
-use simd_aes::SimdAes;
-
-const DEFAULT_SEED: Simd<u8, 16> = Simd::from_array([
-    178, 201, 95, 240, 40, 41, 143, 216,
-    2, 209, 178, 114, 232, 4, 176, 188,
-]);
-
-#[allow(non_snake_case)]
-fn ComputeGlyphHash(data: &[u8]) -> Simd<u8, 16> {
-    let zero = Simd::splat(0);
-    let mut hash = Simd::<u64, 2>::from_array([data.len() as u64, 0]).to_ne_bytes();
-    hash ^= DEFAULT_SEED;
-
-    let mut chunks = data.chunks_exact(16);
-    for chunk in chunks.by_ref() {
-        let chunk: &[u8; 16] = chunk.try_into().unwrap();
-        let value = Simd::from_array(*chunk);
-        hash ^= value;
-        hash = hash.aes_dec(zero);
-        hash = hash.aes_dec(zero);
-        hash = hash.aes_dec(zero);
-        hash = hash.aes_dec(zero);
-    }
-
-    let remainder = chunks.remainder();
-    let mut temp = [0_u8; 16];
-    temp[..remainder.len()].copy_from_slice(remainder);
-    let value = Simd::from_array(temp);
-
-    hash ^= value;
-    hash = hash.aes_dec(zero);
-    hash = hash.aes_dec(zero);
-    hash = hash.aes_dec(zero);
-    hash = hash.aes_dec(zero);
-    return hash;
-}
-
-#[allow(dead_code)]
-fn inv_aes_dec(mut data: Simd<u8, 16>, key: Simd<u8, 16>) -> Simd<u8, 16> {
-    data ^= key;
-    let zero = Simd::splat(0);
-    data = data.aes_dec_last(zero).aes_enc(zero);
-    return data.aes_enc_last(zero);
-}
-
-fn inv_aes_decx4(mut hash: Simd<u8, 16>) -> Simd<u8, 16> {
-    let zero = Simd::splat(0);
-    hash = hash.aes_dec_last(zero);
-    hash = hash.aes_enc(zero);
-    hash = hash.aes_enc(zero);
-    hash = hash.aes_enc(zero);
-    hash = hash.aes_enc(zero);
-    hash = hash.aes_enc_last(zero);
-    return hash;
-}
-
-fn single_prefix(count: usize, target_hash: Simd<u8, 16>) -> Simd<u8, 16> {
-    // The first stage looks like this:
-    //     Hash ^ Seed = dec^4(Count ^ Seed ^ Chunk)
-    // To get the chunk, we need to reverse these:
-    //     dec^-4(Hash ^ Seed) = Count ^ Seed ^ Chunk
-    //     Chunk = dec^4(Hash ^ Seed) ^ Count ^ Seed
-    // To create a one-prefix initialization, we want:
-    //     Hash = Count
-    //     Count = Count + 16
-    let mut hash = target_hash;
-    hash = inv_aes_decx4(hash);
-
-    let prefix_init = Simd::<u64, 2>::from_array([count as u64 + 16, 0]).to_ne_bytes();
-    hash ^= prefix_init;
-    hash ^= DEFAULT_SEED;
-
-    return hash;
-}
-
-fn preimage_prefix_hash(mut hash: Simd<u8, 16>, data: &[u8]) -> Simd<u8, 16> {
-    let chunks = data.len() / 16;
-    let tail = &data[chunks*16..];
-    let mut tail_buf = [0_u8; 16];
-    tail_buf[..tail.len()].copy_from_slice(tail);
-    let value = Simd::from_array(tail_buf);
-
-    hash = inv_aes_decx4(hash);
-    hash ^= value;
-
-    for chunk in data.chunks_exact(16).rev() {
-        let chunk: &[u8; 16] = chunk.try_into().unwrap();
-        let value = Simd::from_array(*chunk);
-        hash = inv_aes_decx4(hash);
-        hash ^= value;
-    }
-
-    return hash;
-}
-
-fn invert_block(mut hash: Simd<u8, 16>, chunk: &[u8]) -> Simd<u8, 16> {
-    let chunk: &[u8; 16] = chunk.try_into().unwrap();
-    let value = Simd::from_array(*chunk);
-    hash = inv_aes_decx4(hash);
-    return hash ^ value;
-}
-
-fn invert_last(suffix: &[u8], mut hash: Simd<u8, 16>) -> Simd<u8, 16> {
-    let mut tail_buf = [0_u8; 16];
-    tail_buf[..suffix.len()].copy_from_slice(suffix);
-    let value = Simd::from_array(tail_buf);
-
-    hash = inv_aes_decx4(hash);
-    hash ^= value;
-    hash = inv_aes_decx4(hash);
-    return hash;
-}
-
-fn concat(prefix: Simd<u8, 16>, target: &[u8]) -> Vec<u8> {
-    let mut image = prefix.to_array().to_vec();
-    image.extend_from_slice(target);
-    image
-}
 
 fn prefix_collision_attack(message: &[u8]) {
-    let mut target_hash = Simd::<u64, 2>::from_array([message.len() as u64, 0]).to_ne_bytes();
-    target_hash ^= DEFAULT_SEED;
-
-    let prefix = single_prefix(message.len(), target_hash);
+    let prefix = prefix_collision(&GlyphHashParams::REFTERM, message);
     println!("Demonstrating prefix attack");
     println!("message: {:x?}", message);
-    println!("hash:    {:x?}", ComputeGlyphHash(b"hello"));
+    println!("hash:    {:x?}", ComputeGlyphHash(message));
     println!("prefix:  {:x?}", prefix);
-    let forgery = concat(prefix, message);
+    let mut forgery = prefix.to_array().to_vec();
+    forgery.extend_from_slice(message);
     println!("forgery: {:x?}", forgery);
     println!("hash:    {:x?}", ComputeGlyphHash(&forgery));
     println!();
 }
 
-fn chosen_prefix(prefix: &[u8]) {
-    let zero = Simd::splat(0);
-    let mut message = prefix.to_vec();
-    let remainder = 16 - (message.len() % 16);
-    message.extend((0..remainder).map(|_| b'A'));
-    message.extend((0..16).map(|_| 0));
-    let hash = ComputeGlyphHash(&message);
-    let pre_current = invert_last(&[], hash);
-    let pre_target = invert_last(&[], zero);
-    let last = message.len() - 16;
-    let suffix = pre_current ^ pre_target;
-    message[last..].copy_from_slice(&suffix.to_array());
+fn chosen_prefix_attack(prefix: &[u8]) {
+    let message = chosen_prefix(&GlyphHashParams::REFTERM, prefix, Block::ZERO);
     println!("Demonstrating chosen prefix attack");
     println!("prefix:  {:x?}", prefix);
     println!("forgery: {:x?}", message);
@@ -163,12 +38,10 @@ fn chosen_prefix(prefix: &[u8]) {
 fn preimage_attack(suffix: &[u8]) {
     println!("Demonstrating preimage attack");
     println!("suffix:    {:x?}", suffix);
-    let target_hash = Simd::splat(0);
+    let target_hash = Block::ZERO;
     println!("goal hash: {:x?}", target_hash);
-    let prefix_hash = preimage_prefix_hash(target_hash, suffix);
-    let preimage_prefix = single_prefix(suffix.len(), prefix_hash);
-    println!("prefix:    {:x?}", preimage_prefix);
-    let message = concat(preimage_prefix, suffix);
+    let message = preimage(&GlyphHashParams::REFTERM, suffix, target_hash);
+    println!("prefix:    {:x?}", &message[..16]);
 
     println!("message:   {:x?}", message);
     println!("hash:      {:x?}", ComputeGlyphHash(&message));
@@ -176,12 +49,12 @@ fn preimage_attack(suffix: &[u8]) {
 
 fn padding_attack() {
     println!("Demonstrating padding attack");
-    println!(r#"message: "",      hash: {:x?}"#, ComputeGlyphHash(b""));
-    println!(r#"message: "\x01",  hash: {:x?}"#, ComputeGlyphHash(b"\x01"));
-    println!(r#"message: "A",     hash: {:x?}"#, ComputeGlyphHash(b"A"));
-    println!(r#"message: "B\x00", hash: {:x?}"#, ComputeGlyphHash(b"B\x00"));
-    println!(r#"message: "BAAAAAAAAAAAAAAA",         hash: {:x?}"#, ComputeGlyphHash(b"BAAAAAAAAAAAAAAA"));
-    println!(r#"message: "CAAAAAAAAAAAAAAA\x00",     hash: {:x?}"#, ComputeGlyphHash(b"CAAAAAAAAAAAAAAA\x00"));
+    for message in [&b""[..], b"A", b"BAAAAAAAAAAAAAAA"] {
+        println!("message: {:x?}, hash: {:x?}", message, ComputeGlyphHash(message));
+        if let Some(forgery) = padding_collision(&GlyphHashParams::REFTERM, message) {
+            println!("forgery: {:x?}, hash: {:x?}", forgery, ComputeGlyphHash(&forgery));
+        }
+    }
     println!();
 }
 
@@ -189,97 +62,159 @@ fn invert_attack(message: &[u8]) {
     println!("Demonstrating invert attack, invert a hash up to 15 bytes");
     println!("Note: due to padding attack, there are actually more messages");
     println!("plaintext: {:x?}", message);
-    let mut hash = ComputeGlyphHash(message);
+    let hash = ComputeGlyphHash(message);
     println!("hash:      {:x?}", hash);
-    hash = inv_aes_decx4(hash);
-    hash ^= DEFAULT_SEED;
-    let mut buffer = hash.to_array();
-    let len = buffer.iter().rposition(|&chr| chr != 0).map_or(0, |x| x + 1);
-    if len == 16 {
+    let Some(recovered) = invert(&GlyphHashParams::REFTERM, hash) else {
         println!("the plaintext mus be shorter than 16 bytes, cannot invert");
         return;
-    }
-    buffer[0] ^= len as u8;
-    let recovered = &buffer[..len];
+    };
     println!("recovered: {:x?}", recovered);
-    println!("hash:      {:x?}", ComputeGlyphHash(recovered));
+    println!("hash:      {:x?}", ComputeGlyphHash(&recovered));
     println!();
 }
 
-pub fn check_alphanum(bytes: Simd<u8, 16>) -> bool {
-    // check if the characters are outside of '0'..'z' range
-    if (bytes - Simd::splat(b'0')).simd_gt(Simd::splat(b'z' - b'0')).any() {
-        return false;
-    }
-    // check if the characters are in of '9'+1..'A'-1 range
-    if (bytes - Simd::splat(b'9' + 1)).simd_lt(Simd::splat(b'A' - (b'9' + 1))).any() {
-        return false;
-    }
-    // check if the characters are in of 'Z'+1..'a'-1 range
-    if (bytes - Simd::splat(b'Z' + 1)).simd_lt(Simd::splat(b'a' - (b'Z' + 1))).any() {
-        return false;
-    }
-    return true;
+/// Master seed used when none is given on the command line
+const DEFAULT_SEED: u64 = 17820195240;
+
+/// Search settings from the command line
+struct Config {
+    /// Master seed all worker seeds are derived from. `--seed entropy`
+    /// picks one from OS entropy, so that independent runs search
+    /// different candidates.
+    seed: u64,
+    /// Number of worker threads per line
+    threads: u64,
+    /// Run only this worker, to replay a result. At most `MAX_STREAM` of
+    /// the random source, e.g. below 2^16 for xorshift.
+    worker: Option<u64>,
+    /// The chosen random source
+    source: Source,
+    /// Only run the statistical tests on this many symbols
+    stats: Option<u64>,
 }
 
-use core::sync::atomic::{AtomicBool, Ordering};
-static FOUND: AtomicBool = AtomicBool::new(false);
-fn find_ascii_zeros(suffix: &[u8], worker: u64) {
-    const ATTACK_BYTES: usize = 6;
-    let mut target_hash = Simd::<u8, 16>::splat(0);
-    let mut bsuffix = suffix;
-    let suffix_len = 16 - ATTACK_BYTES;
-    let mut whole_block = false;
-    if suffix.len() >= suffix_len {
-        target_hash = preimage_prefix_hash(target_hash, &suffix[suffix_len..]);
-        bsuffix = &suffix[..suffix_len];
-        whole_block = true;
+/// Entry points instantiated for one random source
+#[derive(Clone, Copy)]
+struct Source {
+    /// `search_worker`: suffix, master seed, worker index
+    search: fn(&[u8], u64, u64),
+    /// `stats_worker`: master seed, number of symbols
+    stats: fn(u64, u64) -> StatsReport,
+    /// `RandomSource::MAX_STREAM`, the largest worker index
+    max_worker: u64,
+}
+
+impl Source {
+    fn new<R: RandomSource>() -> Source {
+        Source { search: search_worker::<R>, stats: stats_worker::<R>, max_worker: R::MAX_STREAM }
     }
-    let mut controlled = [0u8; 16];
-    let total_len = ATTACK_BYTES + suffix.len();
-    let controlled_bytes = total_len.min(16);
-    let controlled = &mut controlled[..controlled_bytes];
-    controlled[ATTACK_BYTES..].copy_from_slice(bsuffix);
 
-    let seed = Simd::from_array([
-        17820195240, 4041143216,
-        22093178114, 2324176188,
-    ]);
-    let mut rng = SRng::new(seed * Simd::splat(worker + 1));
-    let start = std::time::Instant::now();
+    /// The random source selectable as `--rng name` with `LANES` lanes
+    fn find<const LANES: usize>(name: &str) -> Option<Source>
+    where
+        Width<LANES>: Lanes<Words = [u64; LANES]>,
+    {
+        let sources = [
+            (SRng::<LANES>::NAME, Source::new::<SRng<LANES>>()),
+            (Xoshiro256pp::<LANES>::NAME, Source::new::<Xoshiro256pp<LANES>>()),
+            (AesCtr::<LANES>::NAME, Source::new::<AesCtr<LANES>>()),
+            (Mxm::<LANES>::NAME, Source::new::<Mxm<LANES>>()),
+        ];
+        sources.into_iter().find(|&(rng, _)| rng == name).map(|(_, source)| source)
+    }
+}
 
-    for ii in 0_u64.. {
-        if FOUND.load(Ordering::Relaxed) {
-            return;
-        }
+const USAGE: &str = "usage: refterm-hash-break [--seed N|entropy] [--threads N] [--worker N] \
+    [--rng xorshift|xoshiro256++|aes-ctr|mxm] [--lanes 2|4|8|16] [--stats SYMBOLS]";
 
-        let prefix = rng.random_alphanum();
-        controlled[..6].copy_from_slice(&prefix[..6]);
+fn parse_u64(value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or("missing value")?;
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|err| format!("{value}: {err}"))
+}
 
-        let prefix = {
-            let prefix_hash = if whole_block {
-                invert_block(target_hash, controlled)
-            } else {
-                preimage_prefix_hash(target_hash, controlled)
-            };
-            single_prefix(total_len, prefix_hash)
+impl Config {
+    fn from_args() -> Result<Config, String> {
+        let mut config = Config {
+            seed: DEFAULT_SEED,
+            threads: 16,
+            worker: None,
+            source: Source::new::<SRng>(),
+            stats: None,
         };
+        let mut rng = <SRng>::NAME.to_owned();
+        let mut lanes = srng::DEFAULT_LANES as u64;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => config.seed = match args.next() {
+                    Some(seed) if seed == "entropy" => srng::entropy_seed(),
+                    seed => parse_u64(seed)?,
+                },
+                "--threads" => config.threads = parse_u64(args.next())?.max(1),
+                "--worker" => config.worker = Some(parse_u64(args.next())?),
+                "--rng" => rng = args.next().ok_or("missing value")?,
+                "--lanes" => lanes = parse_u64(args.next())?,
+                "--stats" => config.stats = Some(parse_u64(args.next())?),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        config.source = match lanes {
+            2 => Source::find::<2>(&rng),
+            4 => Source::find::<4>(&rng),
+            8 => Source::find::<8>(&rng),
+            16 => Source::find::<16>(&rng),
+            _ => return Err(format!("unsupported lane count {lanes}")),
+        }.ok_or(format!("unknown random source {rng}"))?;
+        let max_worker = config.source.max_worker;
+        if *config.workers().end() > max_worker {
+            return Err(format!("{rng} has no more than {} disjoint worker streams",
+                max_worker as u128 + 1));
+        }
+        if let Some(samples) = config.stats {
+            let min = min_samples(&Alphabet::ALPHANUM, lanes as usize);
+            if samples < min as u64 {
+                return Err(format!("--stats needs at least {min} symbols with {lanes} lanes"));
+            }
+        }
+        Ok(config)
+    }
 
-        if check_alphanum(prefix) {
-            FOUND.store(true, Ordering::Relaxed);
-            let mut buffer = prefix.to_array().to_vec();
-            buffer.extend_from_slice(&controlled[..6]);
-            buffer.extend_from_slice(suffix);
-            let elapsed = start.elapsed();
-            let mhs = (ii as f64) / 1e6 / elapsed.as_secs_f64();
-            eprintln!("found prefix in {}it {:?} {:3.3}MH/s/core", ii, elapsed, mhs);
-            eprintln!("hash: {:x?}", ComputeGlyphHash(&buffer));
-            println!("{}", core::str::from_utf8(&buffer).unwrap());
-            break;
+    /// The workers to run
+    fn workers(&self) -> core::ops::RangeInclusive<u64> {
+        match self.worker {
+            Some(worker) => worker..=worker,
+            None => 0..=self.threads - 1,
         }
     }
 }
 
+static FOUND: AtomicBool = AtomicBool::new(false);
+fn search_worker<R: RandomSource>(suffix: &[u8], master_seed: u64, worker: u64) {
+    // Workers use disjoint streams split from the master seed
+    let mut rng = Filler::new(R::stream(master_seed, worker));
+    let start = std::time::Instant::now();
+
+    if let Some(found) = find_ascii_zeros(&GlyphHashParams::REFTERM, suffix, &mut rng, &FOUND) {
+        let elapsed = start.elapsed();
+        let mhs = (found.iterations as f64) / 1e6 / elapsed.as_secs_f64();
+        eprintln!("found prefix in {}it {:?} {:3.3}MH/s/core ({})",
+            found.iterations, elapsed, mhs, simd_aes::backend());
+        eprintln!("rng: {} lanes: {} seed: {:#x} worker: {} iterations: {}",
+            R::NAME, R::Width::LANES, master_seed, worker, found.iterations);
+        eprintln!("hash: {:x?}", ComputeGlyphHash(&found.message));
+        println!("{}", core::str::from_utf8(&found.message).unwrap());
+    }
+}
+
+/// Statistical tests of the alphanumeric candidates of worker 0
+fn stats_worker<R: RandomSource>(master_seed: u64, samples: u64) -> StatsReport {
+    run_tests(R::stream(master_seed, 0), &Alphabet::ALPHANUM, samples as usize)
+}
+
 const MESSAGE: &[&[u8]] = &[
     b" Hello Casey!  I hope this message finds you well.",
     b" Please ignore those 22 random chars to the left for now.",
@@ -315,17 +250,35 @@ const MESSAGE: &[&[u8]] = &[
 ];
 
 fn main() {
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    // Each worker's result only depends on the seed and the worker index,
+    // so a result can be replayed with `--rng R --lanes L --seed S --worker W`.
+    eprintln!("master seed: {:#x}", config.seed);
+
+    if let Some(samples) = config.stats {
+        let report = (config.source.stats)(config.seed, samples);
+        println!("{report}");
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
     padding_attack();
     invert_attack(b"Qwerty123");
     prefix_collision_attack(b"hello");
-    chosen_prefix(b"hello");
+    chosen_prefix_attack(b"hello");
     preimage_attack(b"hello");
 
-    const THREADS: u64 = 16;
+    let Config { seed, source: Source { search, .. }, .. } = config;
     for msg in MESSAGE {
         FOUND.store(false, Ordering::Relaxed);
-        let threads = (0..THREADS)
-            .map(|worker| std::thread::spawn(move || find_ascii_zeros(msg, worker)))
+        let threads = config.workers()
+            .map(|worker| std::thread::spawn(move || search(msg, seed, worker)))
             .collect::<Vec<_>>();
         for thread in threads {
             thread.join().unwrap();